@@ -26,7 +26,7 @@ use std::{
 use sysinfo::SystemExt;
 use tokio::sync::{mpsc, oneshot};
 use tokio::task::spawn_blocking;
-use turbosql::{execute, select, Blob, Turbosql};
+use turbosql::{execute, select, select_async, Blob, Turbosql};
 use url::Url;
 use warp::http::{HeaderMap, Method};
 use warp::Filter;
@@ -112,6 +112,7 @@ extern "C" fn rust_insert_files_from_go(json: *const c_char) {
 
 #[derive(Turbosql, Deserialize, Debug, Clone, Default)]
 #[serde(rename_all = "PascalCase")]
+#[turbosql(index(cachekey, startbytepos, endbytepos), database = "cache")]
 struct FileCache {
  #[serde(skip)]
  rowid: Option<i64>,
@@ -195,6 +196,7 @@ struct ResultItem {
  source_query: Option<String>,
  source_query_url: Option<String>,
  source_result_pos: Option<i32>,
+ #[turbosql(created_at)]
  last_scraped: Option<f64>,
 }
 
@@ -225,6 +227,7 @@ struct BookmarkQueryResultItem {
 struct HostAffection {
  #[graphql(skip)]
  rowid: Option<i64>,
+ #[turbosql(index)]
  host: Option<String>,
  affection: Option<i32>,
 }
@@ -232,7 +235,9 @@ struct HostAffection {
 struct Bookmark {
  #[graphql(skip)]
  rowid: Option<i64>,
+ #[turbosql(index)]
  url: Option<String>,
+ #[turbosql(created_at)]
  timestamp: Option<f64>,
 }
 
@@ -256,8 +261,10 @@ struct RcloneItem {
  rowid: Option<i64>,
  #[serde(rename(deserialize = "ID"))]
  id: Option<String>,
+ #[turbosql(index)]
  path: Option<String>,
  name: Option<String>,
+ #[turbosql(sql_type = "INTEGER")]
  size: Option<i53>,
  mime_type: Option<String>,
  mod_time: Option<String>,
@@ -280,7 +287,8 @@ struct Query;
 #[graphql_object]
 impl Query {
  async fn get_bookmarks() -> FieldResult<Vec<BookmarkQueryResultItem>> {
-  Ok(select!(Vec<BookmarkQueryResultItem> r#"
+  Ok(
+   select_async!(Vec<BookmarkQueryResultItem> r#"
    url,
    title,
    host,
@@ -303,7 +311,9 @@ impl Query {
    )
    GROUP BY url
    ORDER BY bookmark_timestamp DESC
-  "#)?)
+  "#)
+   .await?,
+  )
  }
 
  async fn search(query: String, force_scrape: bool) -> FieldResult<Vec<SearchQueryResultItem>> {
@@ -336,7 +346,7 @@ impl Query {
  async fn get_rclone_items(path: String) -> FieldResult<Vec<RcloneItemQueryResultItem>> {
   debug!("getRcloneItems(path:{:#?})", path);
 
-  Ok(select!(Vec<RcloneItemQueryResultItem> r#"
+  Ok(select_async!(Vec<RcloneItemQueryResultItem> r#"
    path,
    name,
    is_dir,
@@ -345,7 +355,7 @@ impl Query {
    size,
    (SELECT SUM(size) FROM rcloneitem rci2 WHERE rci1.is_dir AND rci2.path LIKE ? AND rci2.path LIKE rci1.path || "/%") AS dir_size
    FROM rcloneitem rci1
-   WHERE path = ? || name"#, format!("{}%", path), path)?)
+   WHERE path = ? || name"#, format!("{}%", path), path).await?)
  }
 }
 
@@ -377,7 +387,7 @@ async fn instant_search(query: String) -> FieldResult<Vec<SearchQueryResultItem>
 
  log::info!("match_query = {:?}", match_query);
 
- Ok(select!(Vec<SearchQueryResultItem> r#"
+ Ok(select_async!(Vec<SearchQueryResultItem> r#"
   search_highlighted_url,
   title,
   snippet,
@@ -396,7 +406,7 @@ async fn instant_search(query: String) -> FieldResult<Vec<SearchQueryResultItem>
     host,
     rank
     FROM resultitem2
-    WHERE resultitem2 MATCH ?
+    WHERE resultitem2 MATCH :match_query
     LIMIT -1 OFFSET 0  -- prevents "unable to use function highlight in the requested context"
   ) sq
 
@@ -405,7 +415,7 @@ async fn instant_search(query: String) -> FieldResult<Vec<SearchQueryResultItem>
   GROUP BY sq.url
   ORDER BY bookmarked DESC, hostaffection DESC, MIN(sq.rank)
   LIMIT 30
- "#, match_query)?)
+ "#).await?)
 }
 
 async fn scrape_search(query: String) -> FieldResult<Vec<SearchQueryResultItem>> {
@@ -416,8 +426,6 @@ async fn scrape_search(query: String) -> FieldResult<Vec<SearchQueryResultItem>>
 
  log::info!("scraped {} results", results.len());
 
- let scrapetime = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH)?.as_millis() as f64;
-
  let results = results
   .iter()
   .enumerate()
@@ -425,22 +433,28 @@ async fn scrape_search(query: String) -> FieldResult<Vec<SearchQueryResultItem>>
    source_query: Some(query.clone()),
    source_query_url: Some(format!("https://html.duckduckgo.com/html?q={}", query)),
    source_result_pos: Some(i as i32),
-   last_scraped: Some(scrapetime),
    ..r.clone()
   })
   .collect::<Vec<_>>();
 
- ResultItem::insert_batch(&results);
+ turbosql::spawn_blocking(move || {
+  turbosql::transaction(|| -> turbosql::Result<()> {
+   ResultItem::insert_batch(&results)?;
+
+   for result in &results {
+    execute!(
+     "INSERT INTO resultitem2(url, title, snippet, host) VALUES (?, ?, ?, ?)",
+     result.url,
+     result.title,
+     result.snippet,
+     result.host
+    )?;
+   }
 
- for result in &results {
-  execute!(
-   "INSERT INTO resultitem2(url, title, snippet, host) VALUES (?, ?, ?, ?)",
-   result.url,
-   result.title,
-   result.snippet,
-   result.host
-  )?;
- }
+   Ok(())
+  })
+ })
+ .await?;
 
  // re-do search against database
 
@@ -449,7 +463,7 @@ async fn scrape_search(query: String) -> FieldResult<Vec<SearchQueryResultItem>>
 
  log::info!("match_query = {:?}", match_query);
 
- Ok(select!(Vec<SearchQueryResultItem> r#"
+ Ok(select_async!(Vec<SearchQueryResultItem> r#"
   search_highlighted_url,
   sq.title AS title,
   sq.snippet AS snippet,
@@ -464,18 +478,18 @@ async fn scrape_search(query: String) -> FieldResult<Vec<SearchQueryResultItem>>
    highlight(resultitem2, 2, '<span class="search-highlight">', '</span>') AS title,
    highlight(resultitem2, 3, '<span class="search-highlight">', '</span>') AS snippet,
    url
-   FROM resultitem2(?)
-   WHERE resultitem2.url IN (SELECT DISTINCT url FROM resultitem WHERE source_query = ?)
+   FROM resultitem2(:match_query)
+   WHERE resultitem2.url IN (SELECT DISTINCT url FROM resultitem WHERE source_query = :query)
    LIMIT -1 OFFSET 0  -- prevents "unable to use function highlight in the requested context"
   ) sq
 
-  LEFT JOIN resultitem ON resultitem.url = sq.url AND resultitem.source_query = ?
+  LEFT JOIN resultitem ON resultitem.url = sq.url AND resultitem.source_query = :query
   LEFT JOIN bookmark ON sq.url = bookmark.url
   LEFT JOIN hostaffection ON resultitem.host = hostaffection.host
   GROUP BY sq.url
   ORDER BY bookmarked DESC, hostaffection DESC, rank
   LIMIT 30
- "#,  match_query, query, query)?)
+ "#).await?)
 }
 
 struct Mutations;
@@ -488,13 +502,9 @@ impl Mutations {
     execute!("DELETE FROM hostaffection WHERE host = ?", host)?;
    }
    _ => {
-    let host_affection: Vec<HostAffection> = select!(Vec<HostAffection> "WHERE host = ?", host)?;
-    if host_affection.is_empty() {
-     HostAffection { host: Some(host.clone()), affection: Some(affection), ..Default::default() }
-      .insert()?;
-    } else {
-     execute!("UPDATE hostaffection SET affection = ? WHERE host = ?", affection, host)?;
-    }
+    let host_affection = select!(Option<HostAffection> "WHERE host = ?", host)?.unwrap_or_default();
+    HostAffection { host: Some(host.clone()), affection: Some(affection), ..host_affection }
+     .upsert()?;
    }
   }
   Ok(host)
@@ -508,12 +518,7 @@ impl Mutations {
    true => {
     let bookmark: Vec<Bookmark> = select!(Vec<Bookmark> "WHERE url = ?", url)?;
     if bookmark.is_empty() {
-     Bookmark {
-      url: Some(url.clone()),
-      timestamp: Some(SystemTime::now().duration_since(SystemTime::UNIX_EPOCH)?.as_millis() as f64),
-      ..Default::default()
-     }
-     .insert()?;
+     Bookmark { url: Some(url.clone()), ..Default::default() }.insert()?;
     }
    }
   }
@@ -572,10 +577,12 @@ async fn main() -> anyhow::Result<()> {
  debug!("debug enabled");
  trace!("trace enabled");
 
+ turbosql::open(Default::default())?;
+
  // info!("reading files!");
  // let contents = std::fs::read_to_string("/Users/eden/gcrypt.json")?;
  // let items: Vec<RcloneItem> = serde_json::from_str(&contents)?;
- // RcloneItem::insert_batch(&items);
+ // RcloneItem::insert_batch(&items)?;
  // info!("inserted!");
  // return Ok(());
 
@@ -806,23 +813,55 @@ async fn filedl_head_handler(
  Ok("hi")
 }
 
-struct ByteStream<R>(R);
+/// Streams a blocking reader such as a BlobHandle, reading each chunk on turbosql's blocking
+/// threads so that it doesn't stall the executor.
+enum ByteStream<R> {
+ Idle(R),
+ Reading(turbosql::Task<(R, std::io::Result<Vec<u8>>)>),
+ Done,
+}
 
-impl<R: Read + Unpin> Stream for ByteStream<R> {
+impl<R: Read + Send + Unpin + 'static> Stream for ByteStream<R> {
  // The same as our future above:
  type Item = tokio::io::Result<Bytes>;
  // type Error = std::io::Error;
 
  fn poll_next(
   mut self: Pin<&mut Self>,
-  _cx: &mut futures::task::Context,
+  cx: &mut futures::task::Context,
  ) -> Poll<Option<Self::Item>> {
-  // let mut buf = [0u8; 1];
-  let mut buf = vec![0_u8; 1024 * 1024];
-  let n = self.0.read(&mut buf)?;
-  // eprintln!("n: {:?}", n);
-  buf.truncate(n);
-  Poll::Ready(Some(Ok(Bytes::from(buf))))
+  loop {
+   match std::mem::replace(&mut *self, ByteStream::Done) {
+    ByteStream::Idle(mut reader) => {
+     *self = ByteStream::Reading(turbosql::spawn_blocking(move || {
+      let mut buf = vec![0_u8; 1024 * 1024];
+      let n = reader.read(&mut buf);
+      (
+       reader,
+       n.map(|n| {
+        buf.truncate(n);
+        buf
+       }),
+      )
+     }));
+    }
+    ByteStream::Reading(mut task) => {
+     return match std::future::Future::poll(Pin::new(&mut task), cx) {
+      Poll::Pending => {
+       *self = ByteStream::Reading(task);
+       Poll::Pending
+      }
+      Poll::Ready((_, Ok(buf))) if buf.is_empty() => Poll::Ready(None),
+      Poll::Ready((reader, Ok(buf))) => {
+       *self = ByteStream::Idle(reader);
+       Poll::Ready(Some(Ok(Bytes::from(buf))))
+      }
+      Poll::Ready((_, Err(e))) => Poll::Ready(Some(Err(e))),
+     };
+    }
+    ByteStream::Done => return Poll::Ready(None),
+   }
+  }
 
   // let mut buf2 = vec![0_u8; 0];
   // let mut buf = BytesMut::with_capacity(1024 * 1024);
@@ -903,16 +942,19 @@ async fn filedl_get_handler(
   let path = fullpath.as_str().trim_start_matches("/filedl/");
   let path = urlencoding::decode(path)?;
 
-  let rcloneitem: RcloneItem = select!(RcloneItem "WHERE path = ?", &path).context(here!())?;
+  let rcloneitem: RcloneItem = select_async!(RcloneItem "WHERE path = ?", &path).await.context(here!())?;
   let size = rcloneitem.size.unwrap().as_i64();
   let endbytepos = size - 1;
 
   // let cached_ranges =
   //  select!(Vec<_> "startbytepos, endbytepos FROM filecache WHERE cachekey = ?", &path);
 
+  // leave the bytes in the database; they're streamed below
+
   let filecache: FileCache =
-   match select!(Option<FileCache> "WHERE cachekey = ? AND startbytepos = ? AND endbytepos = ?",
+   match select_async!(Option<FileCache> "rowid, cachekey, startbytepos, endbytepos, NULL AS bytes FROM filecache WHERE cachekey = ? AND startbytepos = ? AND endbytepos = ?",
   &path, 0, endbytepos)
+   .await
    .context(here!())?
    {
     Some(fc) => fc,
@@ -924,8 +966,9 @@ async fn filedl_get_handler(
      })
      .await?;
      info!("file fetched");
-     select!(FileCache "WHERE cachekey = ? AND startbytepos = ? AND endbytepos = ?",
+     select_async!(FileCache "rowid, cachekey, startbytepos, endbytepos, NULL AS bytes FROM filecache WHERE cachekey = ? AND startbytepos = ? AND endbytepos = ?",
     path, 0, endbytepos)
+     .await
      .context(here!())?
     }
    };
@@ -965,8 +1008,10 @@ async fn filedl_get_handler(
    _ => panic!("Unknown byterange"),
   };
 
-  // .body(warp::hyper::Body::wrap_stream(ByteStream(""))),
-  Ok(builder.body(filecache.bytes.context(here!())?[startbyte as usize..].to_owned()))
+  let mut blob = filecache.bytes_blob().context(here!())?;
+  blob.seek(std::io::SeekFrom::Start(startbyte as u64))?;
+
+  Ok(builder.body(warp::hyper::Body::wrap_stream(ByteStream::Idle(blob))))
  }
  .await
  {
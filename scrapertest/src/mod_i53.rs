@@ -1,6 +1,6 @@
 use juniper::{ParseScalarResult, ParseScalarValue, Value};
 use serde::{Deserialize, Serialize};
-use turbosql::{FromSql, FromSqlResult, ToSql, ValueRef};
+use turbosql::{affinity, FromSql, FromSqlResult, ToSql, TurbosqlType, ValueRef};
 use ux::i53 as ux_i53;
 
 impl std::str::FromStr for i53 {
@@ -75,3 +75,7 @@ impl ToSql for i53 {
   Ok(turbosql::ToSqlOutput::Owned(turbosql::Value::Integer(self.0.into())))
  }
 }
+
+impl TurbosqlType for i53 {
+ type Affinity = affinity::Integer;
+}
@@ -0,0 +1,148 @@
+use crate::{Database, Result};
+use rusqlite::DatabaseName;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+/// A `BLOB` value, read and written in place with SQLite's incremental blob I/O instead of being
+/// loaded into memory.
+///
+/// The handle doesn't hold on to a connection: each `read` or `write` opens the blob briefly, so
+/// it's best used with large buffers. Writes can't change the blob's length; to make room, store
+/// e.g. `vec![0; len]` first. Derived structs have `{member}_blob()` for each `Blob` member.
+///
+/// ```rust
+/// # #[cfg(not(feature = "test"))]
+/// # panic!("rustdoc must be run with '--features test'");
+/// use std::io::{Read, Seek, SeekFrom};
+/// use turbosql::{Blob, Turbosql};
+///
+/// #[derive(Turbosql, Default)]
+/// struct BlobDocTest {
+///  rowid: Option<i64>,
+///  bytes: Option<Blob>,
+/// }
+///
+/// let rowid = BlobDocTest { rowid: None, bytes: Some(b"hello world".to_vec()) }.insert()?;
+///
+/// let mut blob = turbosql::BlobHandle::open("blobdoctest", "bytes", rowid)?;
+/// let mut world = String::new();
+/// blob.seek(SeekFrom::Start(6))?;
+/// blob.read_to_string(&mut world)?;
+/// assert!(world == "world");
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct BlobHandle {
+ db: Database,
+ table: String,
+ column: String,
+ rowid: i64,
+ len: u64,
+ pos: u64,
+}
+
+impl BlobHandle {
+ /// Opens `column` of the row `rowid` in `table`, in the default database.
+ pub fn open(table: &str, column: &str, rowid: i64) -> Result<BlobHandle> {
+  BlobHandle::open_in(crate::database(), table, column, rowid)
+ }
+
+ /// Opens `column` of the row `rowid` in `table`, in `db`.
+ pub fn open_in(db: &Database, table: &str, column: &str, rowid: i64) -> Result<BlobHandle> {
+  let len = db.__with_reader(|conn| -> Result<usize> {
+   Ok(conn.blob_open(DatabaseName::Main, table, column, rowid, true)?.len())
+  })?;
+
+  Ok(BlobHandle {
+   db: db.clone(),
+   table: table.to_owned(),
+   column: column.to_owned(),
+   rowid,
+   len: len as u64,
+   pos: 0,
+  })
+ }
+
+ /// Length of the blob in bytes.
+ pub fn len(&self) -> u64 {
+  self.len
+ }
+
+ pub fn is_empty(&self) -> bool {
+  self.len == 0
+ }
+}
+
+// not io::Error::other, which needs Rust 1.74
+#[allow(clippy::io_other_error)]
+fn io_error(e: crate::Error) -> io::Error {
+ io::Error::new(io::ErrorKind::Other, e)
+}
+
+impl Read for BlobHandle {
+ fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+  if self.pos >= self.len || buf.is_empty() {
+   return Ok(0);
+  }
+
+  let n = self
+   .db
+   .__with_reader(|conn| -> Result<usize> {
+    let blob = conn.blob_open(DatabaseName::Main, &self.table, &self.column, self.rowid, true)?;
+    blob.read_at(buf, self.pos as usize)
+   })
+   .map_err(io_error)?;
+
+  self.pos += n as u64;
+  Ok(n)
+ }
+}
+
+impl Write for BlobHandle {
+ /// Writes up to the end of the blob; at the end, returns `Ok(0)`.
+ fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+  let n = buf.len().min(self.len.saturating_sub(self.pos) as usize);
+
+  if n == 0 {
+   return Ok(0);
+  }
+
+  self
+   .db
+   .__with_db(|conn| -> Result<()> {
+    let mut blob =
+     conn.blob_open(DatabaseName::Main, &self.table, &self.column, self.rowid, false)?;
+    blob.write_at(&buf[..n], self.pos as usize)
+   })
+   .map_err(io_error)?;
+
+  self.pos += n as u64;
+  Ok(n)
+ }
+
+ fn flush(&mut self) -> io::Result<()> {
+  Ok(())
+ }
+}
+
+impl Seek for BlobHandle {
+ fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+  let offset = |base: u64, n: i64| match n >= 0 {
+   true => base.checked_add(n as u64),
+   false => base.checked_sub(n.unsigned_abs()),
+  };
+
+  let pos = match pos {
+   SeekFrom::Start(n) => Some(n),
+   SeekFrom::End(n) => offset(self.len, n),
+   SeekFrom::Current(n) => offset(self.pos, n),
+  };
+
+  match pos {
+   Some(pos) => {
+    self.pos = pos;
+    Ok(pos)
+   }
+   None => Err(io::Error::new(io::ErrorKind::InvalidInput, "invalid seek to a negative position")),
+  }
+ }
+}
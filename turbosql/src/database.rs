@@ -0,0 +1,169 @@
+use crate::{default_path, open_db, schema, Config, Connection, SchemaMismatch, TurbosqlError};
+use rusqlite::OpenFlags;
+use std::ops::Deref;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Read-only connections per database, unless set in [`Config::readers`].
+const DEFAULT_READERS: usize = 4;
+
+/// A handle to an open database. Clones are cheap and share the same connection.
+///
+/// Most programs only need the default database (see [`database`](crate::database)), which is
+/// what the macros and derived methods use unless told otherwise: `select!(db, ...)` and
+/// `execute!(db, ...)` run against `db`, and `#[turbosql(database = "name")]` on a struct sends
+/// its derived methods to [`named_database`](crate::named_database).
+/// Every database is migrated to the full schema in `migrations.toml`.
+///
+/// Each database has one connection for writes, and a pool of read-only connections that
+/// `select!` and the derived `select_*` methods use, so that reads don't wait for writes.
+///
+/// ```rust
+/// # #[cfg(not(feature = "test"))]
+/// # panic!("rustdoc must be run with '--features test'");
+/// use turbosql::{select, Config, Database};
+///
+/// let db = Database::open(Config { path: Some(":memory:".into()), ..Default::default() })?;
+/// assert!(select!(db, i64 "1")? == 1);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Clone)]
+pub struct Database {
+ inner: Arc<Inner>,
+}
+
+struct Inner {
+ id: usize,
+ /// `None` while a transaction has the connection checked out; see `transaction.rs`.
+ conn: Mutex<Option<Connection>>,
+ returned: Condvar,
+ /// Idle read-only connections. In-memory databases have none, since they can't be shared.
+ readers: Mutex<Vec<Connection>>,
+ reader_returned: Condvar,
+ has_readers: bool,
+}
+
+impl Database {
+ /// Opens the database at `config.path` and applies migrations.
+ pub fn open(config: Config) -> Result<Database, TurbosqlError> {
+  let path = config.path.clone().unwrap_or_else(|| default_path(None));
+
+  let readers = match path == Path::new(":memory:") {
+   true => 0,
+   false => config.readers.unwrap_or(DEFAULT_READERS),
+  };
+
+  let conn = open_db(Config { path: Some(path.clone()), ..config })?;
+
+  // Readers are opened after migrating, so they see the migrated schema.
+
+  let readers = (0..readers)
+   .map(|_| {
+    Connection::open_with_flags(
+     &path,
+     OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )
+   })
+   .collect::<Result<Vec<_>, _>>()?;
+
+  Ok(Database {
+   inner: Arc::new(Inner {
+    id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+    conn: Mutex::new(Some(conn)),
+    returned: Condvar::new(),
+    has_readers: !readers.is_empty(),
+    readers: Mutex::new(readers),
+    reader_returned: Condvar::new(),
+   }),
+  })
+ }
+
+ /// Compares this database's schema to `migrations.toml`.
+ pub fn verify_schema(&self) -> Result<(), Box<SchemaMismatch>> {
+  let toml_decoded = crate::embedded_migrations_toml();
+  let target_migrations = toml_decoded.migrations_append_only.unwrap_or_default();
+  let target_schema = toml_decoded.target_schema_autogenerated.as_deref();
+
+  self
+   .__with_db(|db| schema::verify_schema(db, &target_migrations, target_schema))
+   .expect("Verify schema")
+ }
+
+ pub(crate) fn id(&self) -> usize {
+  self.inner.id
+ }
+
+ /// Locks the connection, first waiting for any other thread's transaction to finish. A panic
+ /// while it was held leaves SQLite itself consistent, so the poison flag is ignored.
+ pub(crate) fn lock(&self) -> MutexGuard<'_, Option<Connection>> {
+  let conn = self.inner.conn.lock().unwrap_or_else(PoisonError::into_inner);
+  self
+   .inner
+   .returned
+   .wait_while(conn, |conn| conn.is_none())
+   .unwrap_or_else(PoisonError::into_inner)
+ }
+
+ /// Takes the connection for a transaction; other threads wait until it is checked back in.
+ pub(crate) fn checkout(&self) -> Connection {
+  self.lock().take().unwrap()
+ }
+
+ pub(crate) fn checkin(&self, conn: Connection) {
+  *self.inner.conn.lock().unwrap_or_else(PoisonError::into_inner) = Some(conn);
+  self.inner.returned.notify_one();
+ }
+
+ /// Takes an idle read-only connection, waiting if all are in use; `None` if there are none.
+ pub(crate) fn reader(&self) -> Option<Reader> {
+  if !self.inner.has_readers {
+   return None;
+  }
+
+  let readers = self.inner.readers.lock().unwrap_or_else(PoisonError::into_inner);
+  let mut readers = self
+   .inner
+   .reader_returned
+   .wait_while(readers, |readers| readers.is_empty())
+   .unwrap_or_else(PoisonError::into_inner);
+
+  Some(Reader { db: self.clone(), conn: readers.pop() })
+ }
+
+ /// Takes an idle read-only connection without waiting, if there is one.
+ pub(crate) fn idle_reader(&self) -> Option<Reader> {
+  let mut readers = self.inner.readers.lock().unwrap_or_else(PoisonError::into_inner);
+  readers.pop().map(|conn| Reader { db: self.clone(), conn: Some(conn) })
+ }
+}
+
+/// A read-only connection, returned to the pool when dropped.
+pub(crate) struct Reader {
+ db: Database,
+ conn: Option<Connection>,
+}
+
+impl Deref for Reader {
+ type Target = Connection;
+
+ fn deref(&self) -> &Connection {
+  self.conn.as_ref().unwrap()
+ }
+}
+
+impl Drop for Reader {
+ fn drop(&mut self) {
+  let mut readers = self.db.inner.readers.lock().unwrap_or_else(PoisonError::into_inner);
+  readers.extend(self.conn.take());
+  self.db.inner.reader_returned.notify_one();
+ }
+}
+
+impl std::fmt::Debug for Database {
+ fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+  f.debug_struct("Database").field("id", &self.inner.id).finish()
+ }
+}
@@ -0,0 +1,92 @@
+use crate::SchemaMismatch;
+use std::fmt;
+
+/// Errors opening the database; see [`open`](crate::open).
+#[derive(Debug)]
+pub enum TurbosqlError {
+ /// The database file couldn't be created or opened.
+ Io(std::io::Error),
+ /// The migration already applied at `index` differs from the one in `migrations.toml`.
+ MigrationMismatch { index: usize, applied: String, expected: String },
+ /// The database has more migrations applied than this binary knows about,
+ /// e.g. it was last opened by a newer build.
+ DatabaseNewerThanBinary { applied: usize, known: usize },
+ /// The database schema doesn't match `migrations.toml`; see [`SchemaCheck`](crate::SchemaCheck).
+ SchemaMismatch(Box<SchemaMismatch>),
+ /// [`open`](crate::open) was called after the database was already opened.
+ AlreadyOpen,
+ /// Any other error from SQLite.
+ Sqlite(rusqlite::Error),
+}
+
+impl fmt::Display for TurbosqlError {
+ fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+  match self {
+   TurbosqlError::Io(e) => write!(f, "Unable to open Turbosql database file: {}", e),
+   TurbosqlError::MigrationMismatch { index, applied, expected } => write!(
+    f,
+    "Mismatch in Turbosql migrations at index {}: applied {:?}, expected {:?}",
+    index, applied, expected
+   ),
+   TurbosqlError::DatabaseNewerThanBinary { applied, known } => write!(
+    f,
+    "Turbosql database has {} migrations applied, but this binary only knows {}",
+    applied, known
+   ),
+   TurbosqlError::SchemaMismatch(e) => e.fmt(f),
+   TurbosqlError::AlreadyOpen => write!(f, "Turbosql database is already open"),
+   TurbosqlError::Sqlite(e) => e.fmt(f),
+  }
+ }
+}
+
+impl std::error::Error for TurbosqlError {
+ fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+  match self {
+   TurbosqlError::Io(e) => Some(e),
+   TurbosqlError::SchemaMismatch(e) => Some(&**e),
+   TurbosqlError::Sqlite(e) => Some(e),
+   _ => None,
+  }
+ }
+}
+
+impl From<std::io::Error> for TurbosqlError {
+ fn from(e: std::io::Error) -> Self {
+  TurbosqlError::Io(e)
+ }
+}
+
+impl From<rusqlite::Error> for TurbosqlError {
+ fn from(e: rusqlite::Error) -> Self {
+  TurbosqlError::Sqlite(e)
+ }
+}
+
+impl From<Box<SchemaMismatch>> for TurbosqlError {
+ fn from(e: Box<SchemaMismatch>) -> Self {
+  TurbosqlError::SchemaMismatch(e)
+ }
+}
+
+/// A stored value that couldn't be converted to its member's type, naming the column. Derived
+/// selects return it boxed in [`Error::FromSqlConversionFailure`](crate::Error).
+#[derive(Debug)]
+pub struct ColumnError {
+ /// The column that was being read.
+ pub column: &'static str,
+ /// Why the value couldn't be converted.
+ pub source: Box<dyn std::error::Error + Send + Sync + 'static>,
+}
+
+impl fmt::Display for ColumnError {
+ fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+  write!(f, "Invalid value in column {}: {}", self.column, self.source)
+ }
+}
+
+impl std::error::Error for ColumnError {
+ fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+  Some(&*self.source)
+ }
+}
@@ -0,0 +1,204 @@
+use crate::database::Reader;
+use crate::{Connection, Database, Result, Value};
+use rusqlite::Row;
+use std::ops::Deref;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use std::thread;
+
+/// Rows decoded ahead of the consumer, per streaming iterator
+const BUFFERED_ROWS: usize = 64;
+
+/// Rows of a `select!(Iter<T> ...)`, decoded lazily.
+///
+/// The query runs on Turbosql's worker threads (see [`spawn_blocking`](crate::spawn_blocking)),
+/// a few rows ahead of the consumer, and holds its connection until the iterator is exhausted or
+/// dropped. That's one of the database's read-only connections, waiting for one to be free if
+/// other iterators hold them all. In-memory databases have no readers, so there it's the writer,
+/// and other queries wait for the iterator. Inside a transaction, it's the transaction's
+/// connection, which the transaction can't use until the iterator is done.
+///
+/// ```rust
+/// # #[cfg(not(feature = "test"))]
+/// # panic!("rustdoc must be run with '--features test'");
+/// use turbosql::{select, Turbosql};
+///
+/// #[derive(Turbosql, Default)]
+/// struct IterDocTest {
+///  rowid: Option<i64>,
+///  name: Option<String>,
+/// }
+///
+/// for row in select!(Iter<IterDocTest> "ORDER BY rowid")? {
+///  println!("{:?}", row?.name);
+/// }
+/// # Ok::<(), turbosql::Error>(())
+/// ```
+pub struct Iter<T> {
+ db: Database,
+ rows: Option<Receiver<Result<T>>>,
+ done: Option<Receiver<Done>>,
+}
+
+/// What the query's job leaves: the transaction's connection, if it had it, and whether it
+/// panicked.
+type Done = (Option<Connection>, thread::Result<()>);
+
+impl<T> Iterator for Iter<T> {
+ type Item = Result<T>;
+
+ fn next(&mut self) -> Option<Result<T>> {
+  match self.rows.as_ref()?.recv() {
+   Ok(row) => Some(row),
+   Err(_) => {
+    // The query has finished, or panicked, which mustn't pass for the end of the rows.
+    self.rows = None;
+    if let Err(panic) = self.finish() {
+     panic::resume_unwind(panic);
+    }
+    None
+   }
+  }
+ }
+}
+
+impl<T> Iter<T> {
+ /// Waits for the query's job to end, and gives back the transaction's connection.
+ fn finish(&mut self) -> thread::Result<()> {
+  let (conn, result) = match self.done.take() {
+   Some(done) => done.recv().expect("Turbosql iterator job exited without finishing"),
+   None => return Ok(()),
+  };
+  if let Some(conn) = conn {
+   self.db.return_transaction(conn);
+  }
+  result
+ }
+}
+
+impl<T> Drop for Iter<T> {
+ fn drop(&mut self) {
+  self.rows = None; // stops the query
+  self.finish().ok();
+ }
+}
+
+/// The connection an iterator's query runs on.
+enum Source {
+ Reader(Reader),
+ Writer(Checkout),
+ Transaction(Connection),
+}
+
+impl Deref for Source {
+ type Target = Connection;
+
+ fn deref(&self) -> &Connection {
+  match self {
+   Source::Reader(reader) => reader,
+   Source::Writer(checkout) => checkout.conn.as_ref().unwrap(),
+   Source::Transaction(conn) => conn,
+  }
+ }
+}
+
+/// The writer, checked out of the database until dropped.
+struct Checkout {
+ db: Database,
+ conn: Option<Connection>,
+}
+
+impl Drop for Checkout {
+ fn drop(&mut self) {
+  if let Some(conn) = self.conn.take() {
+   self.db.checkin(conn);
+  }
+ }
+}
+
+impl Database {
+ /// Runs `sql` for `select!(Iter<T> ...)`, decoding each row with `f`.
+ #[doc(hidden)]
+ pub fn __iter<T, F>(&self, sql: &str, params: Result<Vec<Value>>, f: F) -> Result<Iter<T>>
+ where
+  T: Send + 'static,
+  F: Fn(&Row) -> Result<T> + Send + 'static,
+ {
+  let params = params?;
+
+  // Wait for the connection here, rather than on a worker, so that iterators waiting for one
+  // can't take up the workers that the ones holding them need.
+
+  let source = match self.lend_transaction() {
+   Some(conn) => Source::Transaction(conn),
+   None => match self.reader() {
+    Some(reader) => Source::Reader(reader),
+    None => Source::Writer(Checkout { db: self.clone(), conn: Some(self.checkout()) }),
+   },
+  };
+
+  let (ready_sender, ready) = sync_channel(1);
+  let (sender, rows) = sync_channel(BUFFERED_ROWS);
+  let (done_sender, done) = sync_channel(1);
+  let sql = sql.to_owned();
+
+  // The job catches its own panics, so that the transaction's connection always comes back.
+
+  drop(crate::spawn_blocking(move || {
+   let result =
+    panic::catch_unwind(AssertUnwindSafe(|| run(&source, &sql, &params, f, ready_sender, sender)));
+   let conn = match source {
+    Source::Transaction(conn) => Some(conn),
+    _ => None,
+   };
+   done_sender.send((conn, result)).ok();
+  }));
+
+  let mut iter = Iter { db: self.clone(), rows: Some(rows), done: Some(done) };
+
+  match ready.recv() {
+   Ok(Ok(())) => Ok(iter),
+   Ok(Err(e)) => Err(e),
+   Err(_) => match iter.finish() {
+    Err(panic) => panic::resume_unwind(panic),
+    Ok(()) => unreachable!("Turbosql iterator job exited without starting the query"),
+   },
+  }
+ }
+}
+
+fn run<T>(
+ conn: &Connection,
+ sql: &str,
+ params: &[Value],
+ f: impl Fn(&Row) -> Result<T>,
+ ready: SyncSender<Result<()>>,
+ sender: SyncSender<Result<T>>,
+) {
+ let mut stmt = match conn.prepare_cached(sql) {
+  Ok(stmt) => stmt,
+  Err(e) => {
+   ready.send(Err(e)).ok();
+   return;
+  }
+ };
+ let mut rows = match stmt.query(params) {
+  Ok(rows) => rows,
+  Err(e) => {
+   ready.send(Err(e)).ok();
+   return;
+  }
+ };
+ ready.send(Ok(())).ok();
+
+ loop {
+  let (row, last) = match rows.next() {
+   Ok(Some(row)) => (f(row), false),
+   Ok(None) => return,
+   Err(e) => (Err(e), true),
+  };
+  if sender.send(row).is_err() || last {
+   return; // iterator dropped, or SQLite error
+  }
+ }
+}
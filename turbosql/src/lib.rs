@@ -37,35 +37,58 @@ use itertools::Itertools;
 use log::{debug, error, info, trace, warn};
 use rusqlite::{Connection, OpenFlags, Statement};
 use serde::Deserialize;
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Mutex;
+use std::sync::{Mutex, MutexGuard, PoisonError};
 
 // re-export
 
 #[doc(hidden)]
 pub use once_cell::sync::Lazy;
+use once_cell::sync::OnceCell;
 #[doc(hidden)]
 pub use rusqlite::{
- params, types::FromSql, types::FromSqlResult, types::ToSql, types::ToSqlOutput, types::Value,
- types::ValueRef, Error, OptionalExtension, Result,
+ params, types::FromSql, types::FromSqlError, types::FromSqlResult, types::Null, types::ToSql,
+ types::ToSqlOutput, types::Value, types::ValueRef, Error, OptionalExtension, Result,
 };
 #[doc(hidden)]
 pub use serde::Serialize;
-pub use turbosql_macros::{execute, select, Turbosql};
+pub use turbosql_macros::{execute, execute_async, select, select_async, Turbosql};
+
+mod blob;
+mod database;
+mod error;
+mod iter;
+mod page;
+mod query;
+mod schema;
+mod task;
+mod transaction;
+mod types;
+pub use blob::BlobHandle;
+pub use database::Database;
+pub use error::{ColumnError, TurbosqlError};
+pub use iter::Iter;
+pub use page::Page;
+#[doc(hidden)]
+pub use page::{__PageSql, __page_cursor};
+pub use query::{Column, Filter, Order, Query};
+pub use schema::{ColumnMismatch, SchemaCheck, SchemaMismatch, TableMismatch};
+#[doc(hidden)]
+pub use task::__to_value;
+pub use task::{spawn_blocking, Task};
+#[doc(hidden)]
+pub use transaction::__with_db;
+pub use transaction::{transaction, Transaction};
+#[doc(hidden)]
+pub use types::{__check_column, __check_json_column, __check_param, __get, __get_json};
+pub use types::{affinity, Json, TurbosqlType};
 
-/// Wrapper for `Vec<u8>` that provides `Read`, `Write` and `Seek` traits.
+/// Type for `BLOB` members. To read or write one without loading it into memory, use the
+/// derived `{member}_blob()` method, which returns a [`BlobHandle`].
 pub type Blob = Vec<u8>;
 
-// #[derive(Debug)]
-// pub struct Blob {
-//  table: String,
-//  column: String,
-//  rowid: i64,
-//  len: i64,
-//  bytes: Option<Vec<u8>>,
-// }
-
 #[derive(Clone, Debug, Deserialize, Default)]
 struct MigrationsToml {
  migrations_append_only: Option<Vec<String>>,
@@ -75,14 +98,13 @@ struct MigrationsToml {
 struct DbPath {
  path: PathBuf,
  opened: bool,
+ schema_check: SchemaCheck,
 }
 
 static __DB_PATH: Lazy<Mutex<DbPath>> = Lazy::new(|| {
+ #[cfg(not(feature = "test"))]
  let cur_exe = match std::env::current_exe() {
-  Ok(path) => match path.file_stem() {
-   Some(stem) => Some(stem.to_str().unwrap().to_string()), // TODO: remove unwrap
-   None => None,
-  },
+  Ok(path) => path.file_stem().map(|stem| stem.to_str().unwrap().to_string()), // TODO: remove unwrap
   Err(_) => None,
  };
 
@@ -94,11 +116,14 @@ static __DB_PATH: Lazy<Mutex<DbPath>> = Lazy::new(|| {
   None => "turbosql.sqlite".to_owned(),
  };
 
- Mutex::new(DbPath { path: Path::new(&path_str).to_owned(), opened: false })
+ Mutex::new(DbPath {
+  path: Path::new(&path_str).to_owned(),
+  opened: false,
+  schema_check: SchemaCheck::default(),
+ })
 });
 
-#[doc(hidden)]
-pub static __TURBOSQL_DB: Lazy<Mutex<Connection>> = Lazy::new(|| {
+fn embedded_migrations_toml() -> MigrationsToml {
  #[cfg(not(feature = "test"))]
  let toml_decoded: MigrationsToml = toml::from_str(include_str!("../../migrations.toml"))
   .expect("Unable to decode embedded migrations.toml");
@@ -106,100 +131,225 @@ pub static __TURBOSQL_DB: Lazy<Mutex<Connection>> = Lazy::new(|| {
  let toml_decoded: MigrationsToml =
   toml::from_str(include_str!("../../test.migrations.toml")).unwrap();
 
- let target_migrations = toml_decoded.migrations_append_only.unwrap_or_else(Vec::new);
+ toml_decoded
+}
 
- let mut db_path = __DB_PATH.lock().unwrap();
+/// Options for [`open`], [`open_named`] and [`Database::open`].
+#[derive(Clone, Debug, Default)]
+pub struct Config {
+ /// Database file. Defaults to the path set with [`set_db_path`], or `{executable name}.sqlite`;
+ /// for [`open_named`], `{executable name}.{name}.sqlite`.
+ pub path: Option<PathBuf>,
+ /// What to do if the schema doesn't match `migrations.toml` after migrating.
+ pub schema_check: SchemaCheck,
+ /// Number of read-only connections for `select!`, so that reads don't wait for writes.
+ /// Defaults to 4. In-memory databases have none.
+ pub readers: Option<usize>,
+}
+
+#[doc(hidden)]
+pub static __TURBOSQL_DB: OnceCell<Database> = OnceCell::new();
+
+static NAMED_DBS: Lazy<Mutex<HashMap<String, &'static Database>>> =
+ Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Opens the default database and applies migrations, returning any error instead of panicking.
+///
+/// Optional: otherwise the database is opened on first use, with the settings from
+/// [`set_db_path`] and [`set_schema_check`], and any error is a panic.
+/// Returns `TurbosqlError::AlreadyOpen` if the database was already opened.
+pub fn open(config: Config) -> Result<(), TurbosqlError> {
+ let mut opened_here = false;
+
+ __TURBOSQL_DB.get_or_try_init(|| {
+  opened_here = true;
+  let config = Config { path: Some(config.path.unwrap_or_else(|| default_path(None))), ..config };
+  let db = Database::open(config)?;
+  __DB_PATH.lock().unwrap().opened = true;
+  Ok::<_, TurbosqlError>(db)
+ })?;
+
+ match opened_here {
+  true => Ok(()),
+  false => Err(TurbosqlError::AlreadyOpen),
+ }
+}
+
+/// Like [`open`], for the database used by structs with `#[turbosql(database = "name")]`.
+pub fn open_named(name: &str, config: Config) -> Result<(), TurbosqlError> {
+ let mut named_dbs = NAMED_DBS.lock().unwrap();
+
+ if named_dbs.contains_key(name) {
+  return Err(TurbosqlError::AlreadyOpen);
+ }
 
+ let config =
+  Config { path: Some(config.path.unwrap_or_else(|| default_path(Some(name)))), ..config };
+ let db = Database::open(config)?;
+ __DB_PATH.lock().unwrap().opened = true;
+ named_dbs.insert(name.to_owned(), Box::leak(Box::new(db)));
+
+ Ok(())
+}
+
+/// The default database, opened on first use if [`open`] wasn't called. Panics if it can't be opened.
+pub fn database() -> &'static Database {
+ __TURBOSQL_DB
+  .get_or_init(|| Database::open(implicit_config(None)).unwrap_or_else(|e| panic!("{}", e)))
+}
+
+/// The database named `name`, opened on first use if [`open_named`] wasn't called.
+/// Panics if it can't be opened.
+pub fn named_database(name: &str) -> &'static Database {
+ let mut named_dbs = NAMED_DBS.lock().unwrap();
+
+ if let Some(db) = named_dbs.get(name) {
+  return db;
+ }
+
+ let db = Database::open(implicit_config(Some(name))).unwrap_or_else(|e| panic!("{}", e));
+ let db = Box::leak(Box::new(db));
+ named_dbs.insert(name.to_owned(), db);
+ db
+}
+
+/// `{executable name}.sqlite`, or `{executable name}.{name}.sqlite`, next to the path set with
+/// [`set_db_path`]. Under the `test` feature, every database is in memory.
+fn default_path(name: Option<&str>) -> PathBuf {
+ let path = __DB_PATH.lock().unwrap().path.clone();
+
+ match name {
+  Some(_) if path == Path::new(":memory:") => path,
+  Some(name) => path.with_extension(format!("{}.sqlite", name)),
+  None => path,
+ }
+}
+
+/// Config for a database opened on first use, with the settings from [`set_db_path`] and
+/// [`set_schema_check`], which can't be changed after this.
+fn implicit_config(name: Option<&str>) -> Config {
+ let path = default_path(name);
+ let mut db_path = __DB_PATH.lock().unwrap();
  db_path.opened = true;
+ Config { path: Some(path), schema_check: db_path.schema_check, readers: None }
+}
+
+fn open_db(config: Config) -> Result<Connection, TurbosqlError> {
+ let toml_decoded = embedded_migrations_toml();
+
+ let target_migrations = toml_decoded.migrations_append_only.unwrap_or_default();
+
+ let path = config.path.unwrap_or_else(|| default_path(None));
+
+ // Surface a missing directory or permissions problem as an I/O error, rather than SQLITE_CANTOPEN
+
+ if path != Path::new(":memory:") {
+  std::fs::OpenOptions::new().read(true).write(true).create(true).truncate(false).open(&path)?;
+ }
 
  // We are handling the mutex, so SQLite can be opened in no-mutex mode; see:
  // http://sqlite.1065341.n5.nabble.com/SQLITE-OPEN-FULLMUTEX-vs-SQLITE-OPEN-NOMUTEX-td104785.html
 
- let conn = Connection::open_with_flags(
-  &db_path.path,
+ let mut conn = Connection::open_with_flags(
+  &path,
   OpenFlags::SQLITE_OPEN_READ_WRITE
    | OpenFlags::SQLITE_OPEN_CREATE
    | OpenFlags::SQLITE_OPEN_NO_MUTEX,
- )
- .expect("rusqlite::Connection::open_with_flags");
-
- conn
-  .execute_batch(
-   r#"
-    PRAGMA auto_vacuum=INCREMENTAL;
-    PRAGMA journal_mode=WAL;
-    PRAGMA wal_autocheckpoint=8000;
-    PRAGMA synchronous=NORMAL;
-   "#,
-  )
-  .expect("Execute PRAGMAs");
-
- let result = conn.query_row(
-  "SELECT sql FROM sqlite_master WHERE name = ?",
-  params!["turbosql_migrations"],
-  |row| {
-   let sql: String = row.get(0).unwrap();
-   Ok(sql)
-  },
- );
-
- match result {
-  Err(rusqlite::Error::QueryReturnedNoRows) => {
-   // no migrations table exists yet, create
-   conn
-    .execute_batch(
-     r#"CREATE TABLE turbosql_migrations (rowid INTEGER PRIMARY KEY, migration TEXT NOT NULL)"#,
-    )
-    .expect("CREATE TABLE turbosql_migrations");
-  }
-  Err(err) => {
-   panic!(err);
-  }
-  Ok(_) => (),
- }
+ )?;
+
+ conn.execute_batch(
+  r#"
+   PRAGMA auto_vacuum=INCREMENTAL;
+   PRAGMA journal_mode=WAL;
+   PRAGMA wal_autocheckpoint=8000;
+   PRAGMA synchronous=NORMAL;
+  "#,
+ )?;
+
+ // create the migrations table if it doesn't exist yet
+
+ conn.execute_batch(
+  r#"CREATE TABLE IF NOT EXISTS turbosql_migrations (rowid INTEGER PRIMARY KEY, migration TEXT NOT NULL)"#,
+ )?;
 
  let applied_migrations = conn
-  .prepare("SELECT migration FROM turbosql_migrations ORDER BY rowid")
-  .unwrap()
-  .query_map(params![], |row| {
-   // let sql: String = row.get(0).unwrap();
-   Ok(row.get(0).unwrap())
-  })
-  .unwrap()
-  .map(|x| x.unwrap())
-  .collect::<Vec<String>>();
-
- // println!("applied_migrations is: {:#?}", applied_migrations);
- // println!("target_migrations is: {:#?}", target_migrations);
-
- // execute migrations
-
- applied_migrations.iter().zip_longest(&target_migrations).for_each(|item| match item {
-  Both(a, b) => {
-   if a != b {
-    panic!("Mismatch in Turbosql migrations!")
+  .prepare("SELECT migration FROM turbosql_migrations ORDER BY rowid")?
+  .query_map(params![], |row| row.get(0))?
+  .collect::<Result<Vec<String>, _>>()?;
+
+ // execute migrations, all or nothing
+
+ let tx = conn.transaction()?;
+
+ for (index, item) in applied_migrations.iter().zip_longest(&target_migrations).enumerate() {
+  match item {
+   Both(applied, expected) if applied != expected => {
+    return Err(TurbosqlError::MigrationMismatch {
+     index,
+     applied: applied.clone(),
+     expected: expected.clone(),
+    })
+   }
+   Both(_, _) => (),
+   Left(_) => {
+    return Err(TurbosqlError::DatabaseNewerThanBinary {
+     applied: applied_migrations.len(),
+     known: target_migrations.len(),
+    })
+   }
+   Right(migration) => {
+    eprintln!("insert -> {:#?}", migration);
+    match tx.execute(migration, params![]) {
+     Ok(_) | Err(rusqlite::Error::ExecuteReturnedResults) => (), // pragmas
+     Err(e) => return Err(e.into()),
+    }
+    tx.execute("INSERT INTO turbosql_migrations(migration) VALUES(?)", params![migration])?;
    }
   }
-  Left(_) => panic!("More migrations are applied than target"),
-  Right(migration) => {
-   eprintln!("insert -> {:#?}", migration);
-   conn.execute(migration, params![]).unwrap();
-   conn
-    .execute("INSERT INTO turbosql_migrations(migration) VALUES(?)", params![migration])
-    .unwrap();
+ }
+
+ tx.commit()?;
+
+ // Enforce #[turbosql(references)] constraints. This is off while migrating, so that tables
+ // can be created in any order.
+
+ conn.execute_batch("PRAGMA foreign_keys=ON;")?;
+
+ // verify schema against target_schema_autogenerated
+
+ if config.schema_check != SchemaCheck::Off {
+  let target_schema = toml_decoded.target_schema_autogenerated.as_deref();
+  match schema::verify_schema(&conn, &target_migrations, target_schema)? {
+   Ok(()) => (),
+   Err(mismatch) if config.schema_check == SchemaCheck::Warn => warn!("{}", mismatch),
+   Err(mismatch) => return Err(mismatch.into()),
   }
- });
+ }
 
- // TODO: verify schema against target_schema_autogenerated
+ Ok(conn)
+}
 
- //    if sql != create_sql {
- //     println!("{}", sql);
- //     println!("{}", create_sql);
- //     panic!("Turbosql sqlite schema does not match! Delete database file to continue.");
- //    }
+/// Compares the database schema to `migrations.toml`. This is done automatically when the database
+/// is opened, according to [`set_schema_check`].
+pub fn verify_schema() -> Result<(), Box<SchemaMismatch>> {
+ database().verify_schema()
+}
 
- Mutex::new(conn)
-});
+/// Set what happens when the database schema doesn't match `migrations.toml` after migrating,
+/// e.g. because the database was edited by hand. Defaults to [`SchemaCheck::Error`].
+///
+/// Must be called before any usage of Turbosql macros or will return an error.
+pub fn set_schema_check(schema_check: SchemaCheck) -> Result<(), anyhow::Error> {
+ let mut db_path = __DB_PATH.lock().unwrap();
+
+ if db_path.opened {
+  return Err(anyhow::anyhow!("Trying to set schema check when DB is already opened"));
+ }
+
+ db_path.schema_check = schema_check;
+
+ Ok(())
+}
 
 /// Set the local path and filename where Turbosql will store the underlying SQLite database.
 ///
@@ -216,3 +366,23 @@ pub fn set_db_path(path: &Path) -> Result<(), anyhow::Error> {
 
  Ok(())
 }
+
+/// For generated methods that need the row's `rowid`, such as `update()`: an error if it's `None`,
+/// as for a row that hasn't been inserted.
+#[doc(hidden)]
+pub fn __require_rowid(rowid: Option<i64>, method: &str) -> Result<i64> {
+ rowid.ok_or_else(|| {
+  Error::ToSqlConversionFailure(
+   format!("{}() requires a rowid; insert the row first", method).into(),
+  )
+ })
+}
+
+/// Milliseconds since the Unix epoch, for timestamps that Turbosql writes, such as
+/// `#[turbosql(soft_delete)]`'s `deleted_at`.
+#[doc(hidden)]
+pub fn __now_millis() -> i64 {
+ std::time::SystemTime::now()
+  .duration_since(std::time::UNIX_EPOCH)
+  .map_or(0, |since_epoch| since_epoch.as_millis() as i64)
+}
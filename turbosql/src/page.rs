@@ -0,0 +1,189 @@
+use crate::{Database, Error, Result, Value};
+use rusqlite::Row;
+use serde_json::json;
+
+/// One page of rows from `select!(Page<T> ...)`, with a cursor for the next one.
+///
+/// The last two parameters are the cursor, an `Option<&str>` or `Option<String>` that's `None`
+/// for the first page, and the page size. When the SQL ends in `ORDER BY` result columns, pages
+/// continue after the previous page's last row (keyset pagination), so rows added meanwhile don't
+/// shift them. Otherwise, pages use `LIMIT`/`OFFSET`. Rows with equal `ORDER BY` values are
+/// ordered by `rowid`, which is added to the query if it doesn't select one, so that none are
+/// skipped. `NULL`s sort first, or last with `DESC`.
+///
+/// ```rust
+/// # #[cfg(not(feature = "test"))]
+/// # panic!("rustdoc must be run with '--features test'");
+/// use turbosql::{select, Page, Turbosql};
+///
+/// #[derive(Turbosql, Default)]
+/// struct PageDocTest {
+///  rowid: Option<i64>,
+///  timestamp: Option<i64>,
+/// }
+///
+/// let mut cursor = None;
+/// loop {
+///  let page = select!(Page<PageDocTest> "ORDER BY timestamp DESC", cursor, 50)?;
+///  // ...page.items...
+///  cursor = match page.next_cursor {
+///   Some(next_cursor) => Some(next_cursor),
+///   None => break,
+///  };
+/// }
+/// # Ok::<(), turbosql::Error>(())
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Page<T> {
+ /// Up to the page size of rows.
+ pub items: Vec<T>,
+ /// An opaque cursor for the following page, or `None` if this is the last one.
+ pub next_cursor: Option<String>,
+}
+
+/// SQL generated by `select!(Page<T> ...)`, whose parameters are followed by the cursor's values
+/// and `LIMIT`.
+#[doc(hidden)]
+#[derive(Debug)]
+pub struct __PageSql {
+ /// For the first page.
+ pub first: &'static str,
+ /// For later pages: after the cursor's `ORDER BY` values, or at its `OFFSET`.
+ pub after: &'static str,
+ /// Result columns in `ORDER BY`, for keyset pagination; empty for `LIMIT`/`OFFSET`.
+ pub keys: &'static [usize],
+}
+
+enum Cursor {
+ After(Vec<Value>),
+ Offset(i64),
+}
+
+impl Cursor {
+ fn encode(&self) -> String {
+  let cursor = match self {
+   Cursor::After(values) => json!({ "after": values.iter().map(to_json).collect::<Vec<_>>() }),
+   Cursor::Offset(offset) => json!({ "offset": offset }),
+  };
+  cursor.to_string().bytes().map(|b| format!("{:02x}", b)).collect()
+ }
+
+ fn decode(cursor: &str) -> Option<Cursor> {
+  let bytes = (0..cursor.len())
+   .step_by(2)
+   .map(|i| u8::from_str_radix(cursor.get(i..i + 2)?, 16).ok())
+   .collect::<Option<Vec<_>>>()?;
+
+  match serde_json::from_slice(&bytes).ok()? {
+   serde_json::Value::Object(cursor) => match (cursor.get("after"), cursor.get("offset")) {
+    (Some(serde_json::Value::Array(values)), None) => {
+     Some(Cursor::After(values.iter().map(from_json).collect::<Option<_>>()?))
+    }
+    (None, Some(offset)) => Some(Cursor::Offset(offset.as_i64()?)),
+    _ => None,
+   },
+   _ => None,
+  }
+ }
+}
+
+fn to_json(value: &Value) -> serde_json::Value {
+ match value {
+  Value::Null => serde_json::Value::Null,
+  Value::Integer(i) => json!(i),
+  Value::Real(f) => json!(f),
+  Value::Text(s) => json!(s),
+  Value::Blob(b) => json!({ "blob": b }),
+ }
+}
+
+fn from_json(value: &serde_json::Value) -> Option<Value> {
+ Some(match value {
+  serde_json::Value::Null => Value::Null,
+  serde_json::Value::Number(n) if n.is_i64() => Value::Integer(n.as_i64()?),
+  serde_json::Value::Number(n) => Value::Real(n.as_f64()?),
+  serde_json::Value::String(s) => Value::Text(s.clone()),
+  serde_json::Value::Object(o) => Value::Blob(serde_json::from_value(o.get("blob")?.clone()).ok()?),
+  _ => return None,
+ })
+}
+
+impl Database {
+ /// Runs `select!(Page<T> ...)`, decoding each row with `f`.
+ #[doc(hidden)]
+ pub fn __page<T, F>(
+  &self,
+  sql: &__PageSql,
+  params: Result<Vec<Value>>,
+  cursor: Option<&str>,
+  size: i64,
+  f: F,
+ ) -> Result<Page<T>>
+ where
+  F: Fn(&Row) -> Result<T>,
+ {
+  let mut params = params?;
+  let size = size.max(0);
+  let keyset = !sql.keys.is_empty();
+
+  let cursor = match cursor {
+   Some(cursor) => Some(Cursor::decode(cursor).ok_or_else(|| {
+    Error::ToSqlConversionFailure(format!("Invalid page cursor {:?}", cursor).into())
+   })?),
+   None => None,
+  };
+
+  // An empty keyset cursor is the start, from a first page of size 0.
+
+  let (query, offset, after) = match cursor {
+   None => (sql.first, 0, Vec::new()),
+   Some(Cursor::After(values)) if keyset && values.is_empty() => (sql.first, 0, values),
+   Some(Cursor::After(values)) if keyset && values.len() == sql.keys.len() => {
+    params.extend(values.iter().cloned());
+    (sql.after, 0, values)
+   }
+   Some(Cursor::Offset(offset)) if !keyset && offset >= 0 => (sql.after, offset, Vec::new()),
+   Some(_) => {
+    return Err(Error::ToSqlConversionFailure("Page cursor is from a different query".into()))
+   }
+  };
+
+  // One more row than the page size tells whether there's a next page.
+
+  params.push(Value::Integer(size + 1));
+  if !keyset {
+   params.push(Value::Integer(offset));
+  }
+
+  self.__with_reader(|db| {
+   let mut stmt = db.prepare_cached(query)?;
+   let mut rows = stmt.query(params)?;
+   let mut items = Vec::new();
+   let mut last_keys = after;
+   let mut more = false;
+
+   while let Some(row) = rows.next()? {
+    if items.len() as i64 == size {
+     more = true;
+     break;
+    }
+    last_keys = sql.keys.iter().map(|i| row.get(*i)).collect::<Result<_>>()?;
+    items.push(f(row)?);
+   }
+
+   let next_cursor = match (more, keyset) {
+    (false, _) => None,
+    (true, true) => Some(Cursor::After(last_keys).encode()),
+    (true, false) => Some(Cursor::Offset(offset + size).encode()),
+   };
+
+   Ok(Page { items, next_cursor })
+  })
+ }
+}
+
+/// Owned cursor for `select!(Page<T> ...)`, so that `select_async!` can move it to its worker.
+#[doc(hidden)]
+pub fn __page_cursor<S: AsRef<str>>(cursor: Option<S>) -> Option<String> {
+ cursor.map(|cursor| cursor.as_ref().to_string())
+}
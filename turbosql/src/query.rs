@@ -0,0 +1,406 @@
+use crate::affinity::Accepts;
+use crate::{Database, Iter, Result, TurbosqlType, Value};
+use rusqlite::Row;
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+/// A column of the `#[derive(Turbosql)]` struct `T`, whose member has type `V`. Derived structs
+/// have one of these for each member, e.g. `RcloneItem::columns().size`, for building a [`Query`].
+pub struct Column<T, V> {
+ name: &'static str,
+ _marker: PhantomData<fn() -> (T, V)>,
+}
+
+/// A condition on the rows of `T`, e.g. `RcloneItem::columns().size.gt(1024)`.
+pub struct Filter<T> {
+ sql: String,
+ params: Vec<Value>,
+ error: Option<Arc<crate::Error>>,
+ _marker: PhantomData<fn() -> T>,
+}
+
+/// A sort order for the rows of `T`, e.g. `RcloneItem::columns().size.desc()`.
+pub struct Order<T> {
+ sql: String,
+ _marker: PhantomData<fn() -> T>,
+}
+
+/// A query on the table of `T`, built at runtime from [`Filter`]s and [`Order`]s. Start one with
+/// the derived `T::query()`. Values are always bound as parameters.
+///
+/// ```rust
+/// # #[cfg(not(feature = "test"))]
+/// # panic!("rustdoc must be run with '--features test'");
+/// use turbosql::Turbosql;
+///
+/// #[derive(Turbosql, Default)]
+/// struct QueryDocTest {
+///  rowid: Option<i64>,
+///  mime_type: Option<String>,
+///  size: Option<i64>,
+/// }
+///
+/// let min_size = 1024;
+/// let c = QueryDocTest::columns();
+/// let files = QueryDocTest::query()
+///  .filter(c.mime_type.like("video/%"))
+///  .filter(c.size.ge(min_size).or(c.size.is_null()))
+///  .order_by(c.size.desc())
+///  .limit(50)
+///  .all()?;
+/// # Ok::<(), turbosql::Error>(())
+/// ```
+pub struct Query<T> {
+ db: Database,
+ from: &'static str,
+ columns: &'static str,
+ from_row: fn(&Row) -> Result<T>,
+ filter: Option<Filter<T>>,
+ order: Vec<String>,
+ limit: Option<i64>,
+ offset: Option<i64>,
+}
+
+impl<T, V> Clone for Column<T, V> {
+ fn clone(&self) -> Self {
+  *self
+ }
+}
+
+impl<T, V> Copy for Column<T, V> {}
+
+impl<T, V> Column<T, V> {
+ #[doc(hidden)]
+ pub const fn __new(name: &'static str) -> Self {
+  Column { name, _marker: PhantomData }
+ }
+
+ /// The column's name in SQL.
+ pub fn name(&self) -> &'static str {
+  self.name
+ }
+
+ fn compare<P>(&self, op: &str, value: P) -> Filter<T>
+ where
+  P: TurbosqlType,
+ {
+  Filter::new(format!("{} {} ?", self.name, op), vec![crate::__to_value(&value)])
+ }
+
+ /// `column = value`. To match `NULL`, use [`is_null`](Column::is_null).
+ pub fn eq<P>(self, value: P) -> Filter<T>
+ where
+  V: TurbosqlType,
+  P: TurbosqlType,
+  V::Affinity: Accepts<P::Affinity>,
+ {
+  self.compare("=", value)
+ }
+
+ /// `column != value`
+ pub fn ne<P>(self, value: P) -> Filter<T>
+ where
+  V: TurbosqlType,
+  P: TurbosqlType,
+  V::Affinity: Accepts<P::Affinity>,
+ {
+  self.compare("!=", value)
+ }
+
+ /// `column < value`
+ pub fn lt<P>(self, value: P) -> Filter<T>
+ where
+  V: TurbosqlType,
+  P: TurbosqlType,
+  V::Affinity: Accepts<P::Affinity>,
+ {
+  self.compare("<", value)
+ }
+
+ /// `column <= value`
+ pub fn le<P>(self, value: P) -> Filter<T>
+ where
+  V: TurbosqlType,
+  P: TurbosqlType,
+  V::Affinity: Accepts<P::Affinity>,
+ {
+  self.compare("<=", value)
+ }
+
+ /// `column > value`
+ pub fn gt<P>(self, value: P) -> Filter<T>
+ where
+  V: TurbosqlType,
+  P: TurbosqlType,
+  V::Affinity: Accepts<P::Affinity>,
+ {
+  self.compare(">", value)
+ }
+
+ /// `column >= value`
+ pub fn ge<P>(self, value: P) -> Filter<T>
+ where
+  V: TurbosqlType,
+  P: TurbosqlType,
+  V::Affinity: Accepts<P::Affinity>,
+ {
+  self.compare(">=", value)
+ }
+
+ /// `column LIKE pattern`, where `%` matches any text and `_` any one character.
+ pub fn like<P>(self, pattern: P) -> Filter<T>
+ where
+  V: TurbosqlType<Affinity = crate::affinity::Text>,
+  P: TurbosqlType<Affinity = crate::affinity::Text>,
+ {
+  self.compare("LIKE", pattern)
+ }
+
+ /// `column IN (values...)`; with no values, matches nothing.
+ pub fn is_in<P, I>(self, values: I) -> Filter<T>
+ where
+  V: TurbosqlType,
+  P: TurbosqlType,
+  V::Affinity: Accepts<P::Affinity>,
+  I: IntoIterator<Item = P>,
+ {
+  let params: Vec<_> = values.into_iter().map(|value| crate::__to_value(&value)).collect();
+  let placeholders = vec!["?"; params.len()].join(", ");
+  Filter::new(format!("{} IN ({})", self.name, placeholders), params)
+ }
+
+ /// `column IS NULL`
+ pub fn is_null(self) -> Filter<T> {
+  Filter::new(format!("{} IS NULL", self.name), Vec::new())
+ }
+
+ /// `column IS NOT NULL`
+ pub fn is_not_null(self) -> Filter<T> {
+  Filter::new(format!("{} IS NOT NULL", self.name), Vec::new())
+ }
+
+ /// Ascending order, `NULL`s first.
+ pub fn asc(self) -> Order<T> {
+  Order { sql: format!("{} ASC", self.name), _marker: PhantomData }
+ }
+
+ /// Descending order, `NULL`s last.
+ pub fn desc(self) -> Order<T> {
+  Order { sql: format!("{} DESC", self.name), _marker: PhantomData }
+ }
+}
+
+impl<T, V> From<Column<T, V>> for Order<T> {
+ fn from(column: Column<T, V>) -> Self {
+  column.asc()
+ }
+}
+
+impl<T> Filter<T> {
+ fn new(sql: String, params: Vec<Result<Value>>) -> Filter<T> {
+  let mut filter = Filter { sql, params: Vec::new(), error: None, _marker: PhantomData };
+  for param in params {
+   match param {
+    Ok(value) => filter.params.push(value),
+    Err(e) => filter.error = filter.error.or_else(|| Some(Arc::new(e))),
+   }
+  }
+  filter
+ }
+
+ fn combine(self, op: &str, other: Filter<T>) -> Filter<T> {
+  let mut params = self.params;
+  params.extend(other.params);
+  Filter {
+   sql: format!("({}) {} ({})", self.sql, op, other.sql),
+   params,
+   error: self.error.or(other.error),
+   _marker: PhantomData,
+  }
+ }
+
+ /// Both this and `other`.
+ pub fn and(self, other: Filter<T>) -> Filter<T> {
+  self.combine("AND", other)
+ }
+
+ /// Either this or `other`.
+ pub fn or(self, other: Filter<T>) -> Filter<T> {
+  self.combine("OR", other)
+ }
+}
+
+/// Not this filter: `!T::columns().size.gt(0)`.
+impl<T> std::ops::Not for Filter<T> {
+ type Output = Filter<T>;
+
+ fn not(self) -> Filter<T> {
+  Filter { sql: format!("NOT ({})", self.sql), ..self }
+ }
+}
+
+impl<T> Query<T> {
+ #[doc(hidden)]
+ pub fn __new(
+  db: &Database,
+  from: &'static str,
+  columns: &'static str,
+  from_row: fn(&Row) -> Result<T>,
+ ) -> Query<T> {
+  Query {
+   db: db.clone(),
+   from,
+   columns,
+   from_row,
+   filter: None,
+   order: Vec::new(),
+   limit: None,
+   offset: None,
+  }
+ }
+
+ /// Only rows matching `filter`, as well as any filters already added.
+ pub fn filter(mut self, filter: Filter<T>) -> Query<T> {
+  self.filter = Some(match self.filter {
+   Some(existing) => existing.and(filter),
+   None => filter,
+  });
+  self
+ }
+
+ /// Sorts by `order`, e.g. `T::columns().size.desc()` or just `T::columns().size`, after any
+ /// orders already added.
+ pub fn order_by(mut self, order: impl Into<Order<T>>) -> Query<T> {
+  self.order.push(order.into().sql);
+  self
+ }
+
+ /// At most `n` rows.
+ pub fn limit(mut self, n: i64) -> Query<T> {
+  self.limit = Some(n);
+  self
+ }
+
+ /// Skips the first `n` rows.
+ pub fn offset(mut self, n: i64) -> Query<T> {
+  self.offset = Some(n);
+  self
+ }
+
+ fn where_clause(&self) -> String {
+  match &self.filter {
+   Some(filter) => format!(" WHERE {}", filter.sql),
+   None => String::new(),
+  }
+ }
+
+ /// The SQL for [`all`](Query::all); values are bound to its `?`s.
+ pub fn sql(&self) -> String {
+  let mut sql = format!("SELECT {} FROM {}{}", self.columns, self.from, self.where_clause());
+
+  if !self.order.is_empty() {
+   sql += &format!(" ORDER BY {}", self.order.join(", "));
+  }
+
+  match (self.limit, self.offset) {
+   (None, None) => (),
+   (_, None) => sql += " LIMIT ?",
+   (_, Some(_)) => sql += " LIMIT ? OFFSET ?",
+  }
+
+  sql
+ }
+
+ fn params(&self, limit: bool) -> Result<Vec<Value>> {
+  let filter = self.filter.as_ref();
+
+  if let Some(e) = filter.and_then(|f| f.error.as_ref()) {
+   return Err(crate::Error::ToSqlConversionFailure(Box::new(Arc::clone(e))));
+  }
+
+  let mut params = filter.map(|f| f.params.clone()).unwrap_or_default();
+
+  if limit {
+   match (self.limit, self.offset) {
+    (None, None) => (),
+    (limit, None) => params.push(Value::Integer(limit.unwrap())),
+    (limit, Some(offset)) => {
+     params.extend(vec![Value::Integer(limit.unwrap_or(-1)), Value::Integer(offset)])
+    }
+   }
+  }
+
+  Ok(params)
+ }
+
+ /// All matching rows.
+ pub fn all(&self) -> Result<Vec<T>> {
+  let (sql, params) = (self.sql(), self.params(true)?);
+
+  self.db.__with_reader(|db| {
+   let mut stmt = db.prepare_cached(&sql)?;
+   let rows = stmt.query_map(params, self.from_row)?.collect::<Result<Vec<_>>>()?;
+   Ok(rows)
+  })
+ }
+
+ /// The first matching row, if any.
+ pub fn first(&self) -> Result<Option<T>> {
+  let query = Query { limit: Some(1), ..self.clone() };
+  let (sql, params) = (query.sql(), query.params(true)?);
+
+  self.db.__with_reader(|db| {
+   let mut stmt = db.prepare_cached(&sql)?;
+   let mut rows = stmt.query_map(params, self.from_row)?;
+   rows.next().transpose()
+  })
+ }
+
+ /// Number of matching rows, regardless of limit and offset.
+ pub fn count(&self) -> Result<i64> {
+  let sql = format!("SELECT COUNT(*) FROM {}{}", self.from, self.where_clause());
+  let params = self.params(false)?;
+
+  self.db.__with_reader(|db| db.prepare_cached(&sql)?.query_row(params, |row| row.get(0)))
+ }
+
+ /// Matching rows, streamed as they are read; see [`Iter`].
+ pub fn iter(&self) -> Result<Iter<T>>
+ where
+  T: Send + 'static,
+ {
+  self.db.__iter(&self.sql(), self.params(true), self.from_row)
+ }
+}
+
+impl<T> Clone for Filter<T> {
+ fn clone(&self) -> Self {
+  Filter {
+   sql: self.sql.clone(),
+   params: self.params.clone(),
+   error: self.error.clone(),
+   _marker: PhantomData,
+  }
+ }
+}
+
+impl<T> Clone for Query<T> {
+ fn clone(&self) -> Self {
+  Query {
+   db: self.db.clone(),
+   from: self.from,
+   columns: self.columns,
+   from_row: self.from_row,
+   filter: self.filter.clone(),
+   order: self.order.clone(),
+   limit: self.limit,
+   offset: self.offset,
+  }
+ }
+}
+
+impl<T> std::fmt::Debug for Query<T> {
+ fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+  f.debug_struct("Query").field("sql", &self.sql()).finish()
+ }
+}
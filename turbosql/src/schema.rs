@@ -0,0 +1,229 @@
+use rusqlite::{params, Connection};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// What to do when the database schema doesn't match `migrations.toml` after migrating.
+/// Set with [`set_schema_check`](crate::set_schema_check).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SchemaCheck {
+ /// Fail to open the database. (default)
+ #[default]
+ Error,
+ /// Log a warning and continue.
+ Warn,
+ /// Don't check.
+ Off,
+}
+
+/// Differences between the live database schema and the one `migrations.toml` produces.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SchemaMismatch {
+ pub missing_tables: Vec<String>,
+ pub unexpected_tables: Vec<String>,
+ pub tables: Vec<TableMismatch>,
+ pub missing_indexes: Vec<String>,
+ pub unexpected_indexes: Vec<String>,
+ pub changed_indexes: Vec<String>,
+}
+
+/// A table that exists in both schemas, but with a different definition.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TableMismatch {
+ pub table: String,
+ pub missing_columns: Vec<String>,
+ pub unexpected_columns: Vec<String>,
+ pub changed_columns: Vec<ColumnMismatch>,
+ pub expected_sql: String,
+ pub actual_sql: String,
+}
+
+/// A column whose type, `NOT NULL`, default or primary key differs.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ColumnMismatch {
+ pub column: String,
+ pub expected: String,
+ pub actual: String,
+}
+
+impl fmt::Display for SchemaMismatch {
+ fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+  write!(f, "Turbosql database schema does not match migrations.toml:")?;
+  for table in &self.missing_tables {
+   write!(f, "\n missing table {}", table)?;
+  }
+  for table in &self.unexpected_tables {
+   write!(f, "\n unexpected table {}", table)?;
+  }
+  for t in &self.tables {
+   write!(f, "\n table {}:", t.table)?;
+   for column in &t.missing_columns {
+    write!(f, "\n  missing column {}", column)?;
+   }
+   for column in &t.unexpected_columns {
+    write!(f, "\n  unexpected column {}", column)?;
+   }
+   for c in &t.changed_columns {
+    write!(f, "\n  column {} is `{}`, expected `{}`", c.column, c.actual, c.expected)?;
+   }
+   if t.missing_columns.is_empty()
+    && t.unexpected_columns.is_empty()
+    && t.changed_columns.is_empty()
+   {
+    write!(f, "\n  is `{}`, expected `{}`", t.actual_sql, t.expected_sql)?;
+   }
+  }
+  for index in &self.missing_indexes {
+   write!(f, "\n missing index {}", index)?;
+  }
+  for index in &self.unexpected_indexes {
+   write!(f, "\n unexpected index {}", index)?;
+  }
+  for index in &self.changed_indexes {
+   write!(f, "\n changed index {}", index)?;
+  }
+  Ok(())
+ }
+}
+
+impl std::error::Error for SchemaMismatch {}
+
+/// `CREATE` statements of all tables and indexes, as stored in `target_schema_autogenerated`
+fn schema(conn: &Connection) -> rusqlite::Result<String> {
+ Ok(
+  conn
+   .prepare(
+    "SELECT sql FROM sqlite_master WHERE type IN ('table', 'index') AND sql IS NOT NULL
+     AND name NOT LIKE 'sqlite_%' ORDER BY sql",
+   )?
+   .query_map(params![], |row| row.get(0))?
+   .collect::<Result<Vec<String>, _>>()?
+   .join("\n"),
+ )
+}
+
+/// name -> sql, for each table or index, except SQLite's own, such as `sqlite_stat1`
+fn objects(conn: &Connection, kind: &str) -> rusqlite::Result<BTreeMap<String, String>> {
+ conn
+  .prepare(
+   "SELECT name, sql FROM sqlite_master WHERE type = ? AND sql IS NOT NULL AND name NOT LIKE 'sqlite_%'",
+  )?
+  .query_map(params![kind], |row| Ok((row.get(0)?, row.get(1)?)))?
+  .collect()
+}
+
+/// column name -> description of everything we compare
+fn columns(conn: &Connection, table: &str) -> rusqlite::Result<BTreeMap<String, String>> {
+ conn
+  .prepare("SELECT name, type, \"notnull\", dflt_value, pk FROM pragma_table_info(?)")?
+  .query_map(params![table], |row| {
+   let (name, ty, notnull, default, pk): (String, String, bool, Option<String>, i64) =
+    (row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?);
+   let mut description = ty;
+   if pk > 0 {
+    description += " PRIMARY KEY";
+   }
+   if notnull {
+    description += " NOT NULL";
+   }
+   if let Some(default) = default {
+    description += &format!(" DEFAULT {}", default);
+   }
+   Ok((name, description))
+  })?
+  .collect()
+}
+
+fn difference<V>(a: &BTreeMap<String, V>, b: &BTreeMap<String, V>) -> Vec<String> {
+ a.keys().filter(|k| !b.contains_key(*k)).cloned().collect()
+}
+
+/// Whether any migration names `name`. Objects that none do were created at runtime, e.g. by
+/// `execute!` or as FTS5 shadow tables, and aren't ours to check.
+fn mentioned(migrations: &[String], name: &str) -> bool {
+ migrations.iter().any(|m| {
+  m.split(|c: char| !(c.is_alphanumeric() || c == '_')).any(|word| word.eq_ignore_ascii_case(name))
+ })
+}
+
+/// Compares `conn` to the schema produced by applying `migrations` to an empty database.
+pub(crate) fn verify_schema(
+ conn: &Connection,
+ migrations: &[String],
+ target_schema: Option<&str>,
+) -> rusqlite::Result<Result<(), Box<SchemaMismatch>>> {
+ let actual_schema = schema(conn)?;
+
+ if Some(actual_schema.as_str()) == target_schema {
+  return Ok(Ok(()));
+ }
+
+ let expected = Connection::open_in_memory()?;
+ expected.execute_batch(
+  "CREATE TABLE turbosql_migrations (rowid INTEGER PRIMARY KEY, migration TEXT NOT NULL)",
+ )?;
+ for migration in migrations {
+  match expected.execute(migration, params![]) {
+   Ok(_) | Err(rusqlite::Error::ExecuteReturnedResults) => (), // pragmas
+   Err(e) => return Err(e),
+  }
+ }
+
+ if schema(&expected)? == actual_schema {
+  return Ok(Ok(()));
+ }
+
+ let (expected_tables, actual_tables) = (objects(&expected, "table")?, objects(conn, "table")?);
+ let (expected_indexes, actual_indexes) = (objects(&expected, "index")?, objects(conn, "index")?);
+
+ let mut tables = Vec::new();
+
+ for (table, expected_sql) in &expected_tables {
+  let actual_sql = match actual_tables.get(table) {
+   Some(actual_sql) if actual_sql != expected_sql => actual_sql,
+   _ => continue,
+  };
+
+  let (expected_columns, actual_columns) = (columns(&expected, table)?, columns(conn, table)?);
+
+  tables.push(TableMismatch {
+   table: table.clone(),
+   missing_columns: difference(&expected_columns, &actual_columns),
+   unexpected_columns: difference(&actual_columns, &expected_columns),
+   changed_columns: expected_columns
+    .iter()
+    .filter_map(|(column, expected)| match actual_columns.get(column) {
+     Some(actual) if actual != expected => Some(ColumnMismatch {
+      column: column.clone(),
+      expected: expected.clone(),
+      actual: actual.clone(),
+     }),
+     _ => None,
+    })
+    .collect(),
+   expected_sql: expected_sql.clone(),
+   actual_sql: actual_sql.clone(),
+  });
+ }
+
+ let unexpected = |names: Vec<String>| -> Vec<String> {
+  names.into_iter().filter(|name| mentioned(migrations, name)).collect()
+ };
+
+ let mismatch = SchemaMismatch {
+  missing_tables: difference(&expected_tables, &actual_tables),
+  unexpected_tables: unexpected(difference(&actual_tables, &expected_tables)),
+  tables,
+  missing_indexes: difference(&expected_indexes, &actual_indexes),
+  unexpected_indexes: unexpected(difference(&actual_indexes, &expected_indexes)),
+  changed_indexes: expected_indexes
+   .iter()
+   .filter(|(index, sql)| matches!(actual_indexes.get(*index), Some(actual) if actual != *sql))
+   .map(|(index, _)| index.clone())
+   .collect(),
+ };
+
+ match mismatch == SchemaMismatch::default() {
+  true => Ok(Ok(())),
+  false => Ok(Err(Box::new(mismatch))),
+ }
+}
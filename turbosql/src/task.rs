@@ -0,0 +1,113 @@
+use crate::{Lazy, Result, ToSql, ToSqlOutput, Value};
+use std::future::Future;
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+
+/// Threads for [`spawn_blocking`]; enough that the readers and the writer of a database can all
+/// be busy at once.
+const WORKERS: usize = 8;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+static POOL: Lazy<Mutex<Sender<Job>>> = Lazy::new(|| {
+ let (sender, receiver) = channel::<Job>();
+ let receiver = Arc::new(Mutex::new(receiver));
+
+ for i in 0..WORKERS {
+  let receiver = Arc::clone(&receiver);
+  thread::Builder::new()
+   .name(format!("turbosql-{}", i))
+   .spawn(move || worker(&receiver))
+   .expect("Unable to spawn Turbosql worker thread");
+ }
+
+ Mutex::new(sender)
+});
+
+fn worker(receiver: &Mutex<Receiver<Job>>) {
+ loop {
+  let job = match receiver.lock().unwrap().recv() {
+   Ok(job) => job,
+   Err(_) => return,
+  };
+  job();
+ }
+}
+
+/// Runs `f` on Turbosql's own thread pool, so that database work doesn't block an async
+/// executor. The returned [`Task`] works with any executor, and re-raises any panic in `f`.
+///
+/// `select_async!`, `execute_async!` and the derived `*_async` methods use this. They run
+/// outside any transaction open on the calling thread; use `spawn_blocking` around the whole
+/// [`transaction`](crate::transaction) instead.
+pub fn spawn_blocking<T, F>(f: F) -> Task<T>
+where
+ T: Send + 'static,
+ F: FnOnce() -> T + Send + 'static,
+{
+ let shared = Arc::new(Mutex::new(Shared { result: None, waker: None }));
+ let task = Task { shared: Arc::clone(&shared) };
+
+ let job = Box::new(move || {
+  let result = panic::catch_unwind(AssertUnwindSafe(f));
+  let mut shared = shared.lock().unwrap();
+  shared.result = Some(result);
+  if let Some(waker) = shared.waker.take() {
+   waker.wake();
+  }
+ });
+
+ POOL.lock().unwrap().send(job).expect("Turbosql worker threads exited");
+
+ task
+}
+
+/// The result of [`spawn_blocking`], once it has run.
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Task<T> {
+ shared: Arc<Mutex<Shared<T>>>,
+}
+
+struct Shared<T> {
+ result: Option<thread::Result<T>>,
+ waker: Option<Waker>,
+}
+
+impl<T> Future for Task<T> {
+ type Output = T;
+
+ fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+  let mut shared = self.shared.lock().unwrap();
+  match shared.result.take() {
+   Some(Ok(value)) => Poll::Ready(value),
+   Some(Err(panic)) => {
+    drop(shared);
+    panic::resume_unwind(panic)
+   }
+   None => {
+    shared.waker = Some(cx.waker().clone());
+    Poll::Pending
+   }
+  }
+ }
+}
+
+/// Converts a `select_async!` or `execute_async!` parameter to an owned value, so that it can
+/// be moved to the worker thread.
+#[doc(hidden)]
+pub fn __to_value<T: ToSql + ?Sized>(value: &T) -> Result<Value> {
+ Ok(match value.to_sql()? {
+  ToSqlOutput::Borrowed(value) => value.into(),
+  ToSqlOutput::Owned(value) => value,
+  ToSqlOutput::ZeroBlob(len) => Value::Blob(vec![0; len as usize]),
+  _ => {
+   return Err(crate::Error::ToSqlConversionFailure(
+    "Unsupported parameter type for an async query".into(),
+   ))
+  }
+ })
+}
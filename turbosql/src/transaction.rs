@@ -0,0 +1,216 @@
+use crate::{Connection, Database, Result};
+use std::cell::RefCell;
+use std::marker::PhantomData;
+
+// While a transaction is open, the thread that opened it holds the database's connection here,
+// along with the database id and current savepoint depth, so every Turbosql call on that thread
+// joins it. An iterator over a query in the transaction takes the connection until it's done.
+
+thread_local! {
+ // not `const { ... }`, which needs Rust 1.59
+ #[allow(clippy::missing_const_for_thread_local)]
+ static TRANSACTIONS: RefCell<Vec<(usize, Option<Connection>, usize)>> = RefCell::new(Vec::new());
+}
+
+/// Runs `f` with the default database's connection for the current thread.
+#[doc(hidden)]
+pub fn __with_db<T>(f: impl FnOnce(&Connection) -> T) -> T {
+ crate::database().__with_db(f)
+}
+
+impl Database {
+ /// Runs `f` with the connection for the current thread: the open transaction's, if any.
+ #[doc(hidden)]
+ pub fn __with_db<T>(&self, f: impl FnOnce(&Connection) -> T) -> T {
+  TRANSACTIONS.with(|txs| match txs.borrow().iter().find(|(id, _, _)| *id == self.id()) {
+   Some((_, conn, _)) => f(tx_conn(conn)),
+   None => f(self.lock().as_ref().unwrap()),
+  })
+ }
+
+ /// Like `__with_db`, but on an idle read-only connection unless this thread has a transaction
+ /// open on this database, so that it sees its own uncommitted writes. If iterators hold all the
+ /// readers, this uses the writer.
+ #[doc(hidden)]
+ pub fn __with_reader<T>(&self, f: impl FnOnce(&Connection) -> T) -> T {
+  TRANSACTIONS.with(|txs| match txs.borrow().iter().find(|(id, _, _)| *id == self.id()) {
+   Some((_, conn, _)) => f(tx_conn(conn)),
+   None => match self.idle_reader() {
+    Some(reader) => f(&reader),
+    None => f(self.lock().as_ref().unwrap()),
+   },
+  })
+ }
+
+ /// Takes the connection of the transaction this thread has open on this database, if any, for
+ /// an iterator; it must be given back with [`return_transaction`](Self::return_transaction).
+ pub(crate) fn lend_transaction(&self) -> Option<Connection> {
+  TRANSACTIONS.with(|txs| {
+   let mut txs = txs.borrow_mut();
+   let (_, conn, _) = txs.iter_mut().find(|(id, _, _)| *id == self.id())?;
+   tx_conn(conn);
+   conn.take()
+  })
+ }
+
+ /// Gives back a connection from [`lend_transaction`](Self::lend_transaction). On a thread without
+ /// the transaction, it's rolled back instead.
+ pub(crate) fn return_transaction(&self, conn: Connection) {
+  TRANSACTIONS.with(|txs| {
+   match txs.borrow_mut().iter_mut().find(|(id, conn, _)| *id == self.id() && conn.is_none()) {
+    Some((_, lent, _)) => *lent = Some(conn),
+    None => {
+     conn.execute_batch("ROLLBACK").ok();
+     self.checkin(conn);
+    }
+   }
+  })
+ }
+
+ /// Begins a transaction on this database; see [`Transaction::begin`].
+ pub fn begin(&self) -> Result<Transaction> {
+  TRANSACTIONS.with(|txs| {
+   let mut txs = txs.borrow_mut();
+   let depth = match txs.iter_mut().find(|(id, _, _)| *id == self.id()) {
+    Some((_, conn, depth)) => {
+     tx_conn(conn).execute_batch(&format!("SAVEPOINT turbosql_{}", *depth + 1))?;
+     *depth += 1;
+     *depth
+    }
+    None => {
+     let conn = self.checkout();
+     if let Err(e) = conn.execute_batch("BEGIN") {
+      self.checkin(conn);
+      return Err(e);
+     }
+     txs.push((self.id(), Some(conn), 0));
+     0
+    }
+   };
+   Ok(Transaction { db: self.clone(), depth, finished: false, _not_send: PhantomData })
+  })
+ }
+
+ /// Runs `f` inside a transaction on this database; see [`transaction`].
+ pub fn transaction<T, E, F>(&self, f: F) -> Result<T, E>
+ where
+  F: FnOnce() -> Result<T, E>,
+  E: From<crate::Error>,
+ {
+  let tx = self.begin()?;
+  let value = f()?;
+  tx.commit()?;
+  Ok(value)
+ }
+}
+
+/// The transaction's connection, unless an iterator has it.
+fn tx_conn(conn: &Option<Connection>) -> &Connection {
+ conn.as_ref().expect("Turbosql transaction is in use by an iterator; finish or drop it first")
+}
+
+/// Guard for an open transaction. Rolls back when dropped unless [`commit`](Transaction::commit)ted.
+///
+/// All `select!`, `execute!` and derived methods called on this thread while the guard is alive
+/// use the transaction's connection; other threads block until it is finished.
+/// Transactions may be nested, in which case the inner ones are SQLite savepoints.
+///
+/// ```rust
+/// # #[cfg(not(feature = "test"))]
+/// # panic!("rustdoc must be run with '--features test'");
+/// let tx = turbosql::Transaction::begin()?;
+/// assert!(turbosql::select!(i64 "1")? == 1);
+/// tx.commit()?;
+/// # Ok::<(), turbosql::Error>(())
+/// ```
+pub struct Transaction {
+ db: Database,
+ depth: usize,
+ finished: bool,
+ _not_send: PhantomData<*const ()>,
+}
+
+impl Transaction {
+ /// Begins a transaction on the default database, or a savepoint if one is already open on
+ /// this thread.
+ pub fn begin() -> Result<Transaction> {
+  crate::database().begin()
+ }
+
+ /// Commits the transaction (or releases the savepoint).
+ pub fn commit(mut self) -> Result<()> {
+  self.finish(true)
+ }
+
+ /// Rolls back the transaction (or to the savepoint).
+ pub fn rollback(mut self) -> Result<()> {
+  self.finish(false)
+ }
+
+ fn finish(&mut self, commit: bool) -> Result<()> {
+  self.finished = true;
+
+  TRANSACTIONS.with(|txs| {
+   let mut txs = txs.borrow_mut();
+   let index = txs
+    .iter()
+    .position(|(id, _, _)| *id == self.db.id())
+    .expect("Turbosql transaction finished on wrong thread");
+   let (_, conn, depth) = &mut txs[index];
+   let conn = tx_conn(conn);
+
+   assert!(*depth == self.depth, "Turbosql transactions must be finished innermost first");
+
+   if self.depth > 0 {
+    *depth -= 1;
+    return conn.execute_batch(&match commit {
+     true => format!("RELEASE turbosql_{}", self.depth),
+     false => format!("ROLLBACK TO turbosql_{0}; RELEASE turbosql_{0}", self.depth),
+    });
+   }
+
+   let result = match commit {
+    true => conn.execute_batch("COMMIT"),
+    false => conn.execute_batch("ROLLBACK"),
+   };
+
+   // If COMMIT failed, SQLite leaves the transaction open; don't hand it to anyone else.
+
+   if result.is_err() && commit {
+    conn.execute_batch("ROLLBACK").ok();
+   }
+
+   let (_, conn, _) = txs.remove(index);
+   self.db.checkin(conn.unwrap());
+   result
+  })
+ }
+}
+
+impl Drop for Transaction {
+ fn drop(&mut self) {
+  if !self.finished {
+   self.finish(false).ok();
+  }
+ }
+}
+
+/// Runs `f` inside a transaction, committing if it returns `Ok` and rolling back if it returns `Err` or panics.
+///
+/// ```rust
+/// # #[cfg(not(feature = "test"))]
+/// # panic!("rustdoc must be run with '--features test'");
+/// let one = turbosql::transaction(|| {
+///  let one = turbosql::select!(i64 "1")?;
+///  Ok::<_, turbosql::Error>(one)
+/// })?;
+/// assert!(one == 1);
+/// # Ok::<(), turbosql::Error>(())
+/// ```
+pub fn transaction<T, E, F>(f: F) -> Result<T, E>
+where
+ F: FnOnce() -> Result<T, E>,
+ E: From<crate::Error>,
+{
+ crate::database().transaction(f)
+}
@@ -0,0 +1,253 @@
+use crate::{
+ ColumnError, Error, FromSql, FromSqlResult, Result, ToSql, ToSqlOutput, Value, ValueRef,
+};
+use rusqlite::types::{FromSqlError, Null};
+use rusqlite::Row;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Marker types for the SQLite column types that Turbosql generates.
+pub mod affinity {
+ /// A SQLite column type, as written in generated `CREATE TABLE`/`ALTER TABLE` migrations.
+ pub trait Affinity {
+  const SQL_TYPE: &'static str;
+ }
+
+ macro_rules! affinity {
+  ($($(#[$meta:meta])* $name:ident => $sql_type:literal,)*) => {
+   $(
+    $(#[$meta])*
+    #[derive(Debug)]
+    pub enum $name {}
+
+    impl Affinity for $name {
+     const SQL_TYPE: &'static str = $sql_type;
+    }
+   )*
+  };
+ }
+
+ affinity! {
+  /// `INTEGER`
+  Integer => "INTEGER",
+  /// `REAL`
+  Real => "REAL",
+  /// `TEXT`
+  Text => "TEXT",
+  /// `BLOB`
+  Blob => "BLOB",
+  /// `BOOLEAN` (stored as an integer, 0 or 1)
+  Boolean => "BOOLEAN",
+  /// Values whose type is only known at runtime, such as `Value` and `Null`. Never a column type;
+  /// parameters of this affinity are accepted for any column.
+  Any => "ANY",
+ }
+
+ /// Column affinities that parameters of affinity `A` can be compared with or assigned to,
+ /// e.g. an `Integer` parameter for a `Real` column. `select!` and `execute!` check this at
+ /// compile time:
+ ///
+ /// ```rust,compile_fail
+ /// # #[cfg(not(feature = "test"))]
+ /// # panic!("rustdoc must be run with '--features test'");
+ /// use turbosql::{select, Turbosql};
+ ///
+ /// #[derive(Turbosql, Default)]
+ /// struct AcceptsDocTest {
+ ///  rowid: Option<i64>,
+ ///  name: Option<String>,
+ /// }
+ ///
+ /// select!(Vec<AcceptsDocTest> "WHERE name = ?", 1_i32);
+ /// ```
+ ///
+ /// ```rust,compile_fail
+ /// # #[cfg(not(feature = "test"))]
+ /// # panic!("rustdoc must be run with '--features test'");
+ /// # use turbosql::{select, Turbosql};
+ /// # #[derive(Turbosql, Default)]
+ /// # struct AcceptsDocTest {
+ /// #  rowid: Option<i64>,
+ /// #  name: Option<String>,
+ /// # }
+ /// select!(Vec<AcceptsDocTest> "LIMIT ?", "10".to_string());
+ /// ```
+ pub trait Accepts<A: Affinity>: Affinity {}
+
+ impl Accepts<Integer> for Integer {}
+ impl Accepts<Boolean> for Integer {}
+ impl Accepts<Real> for Real {}
+ impl Accepts<Integer> for Real {}
+ impl Accepts<Text> for Text {}
+ impl Accepts<Blob> for Blob {}
+ impl Accepts<Boolean> for Boolean {}
+ impl Accepts<Integer> for Boolean {}
+ impl<A: Affinity> Accepts<Any> for A {}
+}
+
+use affinity::Affinity;
+
+/// A Rust type that can be stored in a `#[derive(Turbosql)]` column.
+///
+/// Implement this, along with `ToSql` and `FromSql`, to use your own types (newtypes, timestamps,
+/// UUIDs...) as members. Members of types Turbosql doesn't already know must also name their
+/// column type with `sql_type`. `#[derive(Turbosql)]` needs it to write the migration, which
+/// happens as the derive expands, before trait impls are resolved; `Affinity` then checks it at
+/// compile time:
+///
+/// ```rust
+/// # #[cfg(not(feature = "test"))]
+/// # panic!("rustdoc must be run with '--features test'");
+/// use turbosql::{affinity, FromSql, FromSqlResult, ToSql, ToSqlOutput, Turbosql, TurbosqlType, ValueRef};
+///
+/// #[derive(Default)]
+/// struct Cents(i64);
+///
+/// impl TurbosqlType for Cents {
+///  type Affinity = affinity::Integer;
+/// }
+///
+/// impl ToSql for Cents {
+///  fn to_sql(&self) -> turbosql::Result<ToSqlOutput<'_>> {
+///   self.0.to_sql()
+///  }
+/// }
+///
+/// impl FromSql for Cents {
+///  fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+///   Ok(Cents(value.as_i64()?))
+///  }
+/// }
+///
+/// #[derive(Turbosql, Default)]
+/// struct PurchaseDocTest {
+///  rowid: Option<i64>,
+///  #[turbosql(sql_type = "INTEGER")]
+///  price: Option<Cents>,
+/// }
+/// ```
+pub trait TurbosqlType: ToSql {
+ /// The SQLite column type used for this Rust type.
+ type Affinity: Affinity;
+}
+
+macro_rules! turbosql_type {
+ ($affinity:ident: $($t:ty),*) => {
+  $(
+   impl TurbosqlType for $t {
+    type Affinity = affinity::$affinity;
+   }
+  )*
+ };
+}
+
+turbosql_type!(Integer: i8, i16, i32, i64, isize, u8, u16, u32);
+turbosql_type!(Real: f64);
+turbosql_type!(Text: String, str);
+turbosql_type!(Blob: Vec<u8>, [u8]);
+turbosql_type!(Boolean: bool);
+turbosql_type!(Any: Value, Null, ToSqlOutput<'_>);
+
+impl<T: TurbosqlType> TurbosqlType for Option<T> {
+ type Affinity = T::Affinity;
+}
+
+impl<T: TurbosqlType + ?Sized> TurbosqlType for &T {
+ type Affinity = T::Affinity;
+}
+
+impl<T: TurbosqlType + ?Sized> TurbosqlType for Box<T> {
+ type Affinity = T::Affinity;
+}
+
+impl<T: TurbosqlType + ?Sized> TurbosqlType for std::rc::Rc<T> {
+ type Affinity = T::Affinity;
+}
+
+impl<T: TurbosqlType + ?Sized> TurbosqlType for std::sync::Arc<T> {
+ type Affinity = T::Affinity;
+}
+
+impl<T: TurbosqlType + ToOwned + ?Sized> TurbosqlType for std::borrow::Cow<'_, T> {
+ type Affinity = T::Affinity;
+}
+
+/// Stores any `Serialize + Deserialize` value as JSON `TEXT`, including JSON `null`. Use
+/// `Option<Json<T>>` for SQL `NULL`.
+///
+/// `#[turbosql(json)]` members use this automatically, storing `None` as SQL `NULL`; wrap values
+/// in it yourself to bind them as parameters or read them from `select!`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Json<T>(pub T);
+
+impl<T: Serialize> ToSql for Json<T> {
+ fn to_sql(&self) -> Result<ToSqlOutput<'_>> {
+  Ok(ToSqlOutput::Owned(Value::Text(
+   serde_json::to_string(&self.0).map_err(|e| Error::ToSqlConversionFailure(Box::new(e)))?,
+  )))
+ }
+}
+
+impl<T: DeserializeOwned> FromSql for Json<T> {
+ fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+  match value {
+   ValueRef::Null => serde_json::from_value(serde_json::Value::Null),
+   ValueRef::Text(text) => serde_json::from_slice(text),
+   _ => return Err(FromSqlError::InvalidType),
+  }
+  .map(Json)
+  .map_err(|e| FromSqlError::Other(Box::new(e)))
+ }
+}
+
+impl<T: Serialize> TurbosqlType for Json<T> {
+ type Affinity = affinity::Text;
+}
+
+/// Reads column `idx`, naming `column` in the error if its value can't be converted. (SQLite's
+/// own type mismatch errors already name it.)
+#[doc(hidden)]
+pub fn __get<T: FromSql>(row: &Row, idx: usize, column: &'static str) -> Result<T> {
+ row.get(idx).map_err(|e| match e {
+  Error::FromSqlConversionFailure(idx, ty, source) => {
+   Error::FromSqlConversionFailure(idx, ty, Box::new(ColumnError { column, source }))
+  }
+  Error::IntegralValueOutOfRange(idx, value) => Error::FromSqlConversionFailure(
+   idx,
+   rusqlite::types::Type::Integer,
+   Box::new(ColumnError { column, source: Box::new(FromSqlError::OutOfRange(value)) }),
+  ),
+  e => e,
+ })
+}
+
+/// Reads a `#[turbosql(json)]` column. A JSON `null` that `T` can't hold, such as the `'null'`
+/// default of rows from before the column was added, is an error naming the column.
+#[doc(hidden)]
+pub fn __get_json<T: DeserializeOwned>(row: &Row, idx: usize, column: &'static str) -> Result<T> {
+ Ok(__get::<Json<T>>(row, idx, column)?.0)
+}
+
+/// Compile-time check, emitted by `#[derive(Turbosql)]` for `#[turbosql(json)]` members.
+#[doc(hidden)]
+pub fn __check_json_column<T: Serialize + DeserializeOwned>() {}
+
+/// Compile-time check, emitted by `#[derive(Turbosql)]` for every column, that the member's type
+/// can be read and written, and matches the column type in the generated migrations.
+#[doc(hidden)]
+pub fn __check_column<T, A>()
+where
+ T: TurbosqlType<Affinity = A> + crate::FromSql,
+ A: Affinity,
+{
+}
+
+/// Compile-time check, emitted by `select!` and `execute!` for each parameter whose column they
+/// can tell from the SQL, that the parameter's type fits the column's affinity `A`.
+#[doc(hidden)]
+pub fn __check_param<A, T>(_: &T)
+where
+ A: affinity::Accepts<T::Affinity>,
+ T: TurbosqlType + ?Sized,
+{
+}
@@ -1,4 +1,4 @@
-use turbosql::{execute, select, Blob, Turbosql};
+use turbosql::{execute, select, Blob, Page, Turbosql};
 
 #[derive(Turbosql, Default, Debug, PartialEq, Clone)]
 struct PersonIntegrationTest {
@@ -35,8 +35,8 @@ fn it_works() {
    ))
  );
 
- // assert!(select!(Vec<i64> "SELECT 1").unwrap() == Some(1));
- // assert!(select!(Option<i64> "SELECT 1").unwrap() == Some(1));
+ assert!(select!(Vec<i64> "SELECT 1").unwrap() == vec![1]);
+ assert!(select!(Option<i64> "SELECT 1").unwrap() == Some(1));
 
  assert!(select!(PersonIntegrationTest).unwrap() == row);
  assert!(select!(Vec<PersonIntegrationTest>).unwrap() == vec![row.clone()]);
@@ -52,14 +52,1307 @@ fn it_works() {
 
  assert!(select!(PersonIntegrationTest "WHERE age = 41").is_err());
  assert!(select!(Vec<PersonIntegrationTest> "WHERE age = 41").unwrap() == vec![]);
- assert!(select!(Option<PersonIntegrationTest> "WHERE age = 41").unwrap() == None);
+ assert!(select!(Option<PersonIntegrationTest> "WHERE age = 41").unwrap().is_none());
 
  assert!(select!(i64 "SELECT age FROM personintegrationtest").unwrap() == row.age.unwrap());
  assert!(select!(i64 "age FROM personintegrationtest").unwrap() == row.age.unwrap());
  assert!(select!(i64 "age FROM personintegrationtest WHERE FALSE").is_err());
- // assert!(select!(Vec<i64> "age FROM personintegrationtest").unwrap() == row.age.unwrap());
- // assert!(select!(Option<i64> "age FROM personintegrationtest").unwrap() == row.age);
- // assert!(select!(String "name FROM personintegrationtest").unwrap() == row.name.unwrap());
+ assert!(select!(Vec<i64> "age FROM personintegrationtest").unwrap() == vec![row.age.unwrap()]);
+ assert!(select!(Option<i64> "age FROM personintegrationtest").unwrap() == row.age);
+ assert!(select!(String "name FROM personintegrationtest").unwrap() == row.name.unwrap());
+}
+
+#[derive(Turbosql, Default, Debug, PartialEq, Clone)]
+struct MutationIntegrationTest {
+ rowid: Option<i64>,
+ name: Option<String>,
+ age: Option<i64>,
+}
+
+#[test]
+fn update_upsert_delete() {
+ let row = MutationIntegrationTest { rowid: None, name: Some("Amy".to_string()), age: Some(30) };
+ row.insert().unwrap();
+
+ let mut row = select!(MutationIntegrationTest "WHERE name = ?", row.name).unwrap();
+ row.age = Some(31);
+ assert!(row.update().unwrap() == 1);
+ assert!(select!(MutationIntegrationTest "WHERE rowid = ?", row.rowid).unwrap() == row);
+
+ row.name = Some("Amelia".to_string());
+ assert!(row.upsert().unwrap() == row.rowid.unwrap());
+ assert!(select!(Vec<MutationIntegrationTest>).unwrap() == vec![row.clone()]);
+
+ let new_row = MutationIntegrationTest { rowid: None, name: Some("Ben".to_string()), age: None };
+ let rowid = new_row.upsert().unwrap();
+ assert!(select!(MutationIntegrationTest "WHERE rowid = ?", rowid).unwrap().name == new_row.name);
+ assert!(select!(Vec<MutationIntegrationTest>).unwrap().len() == 2);
+
+ // rows that were never inserted have no rowid to update or delete
+ assert!(new_row.update().is_err());
+ assert!(new_row.delete().is_err());
+
+ assert!(row.delete().unwrap() == 1);
+ assert!(select!(Option<MutationIntegrationTest> "WHERE rowid = ?", row.rowid).unwrap().is_none());
+ assert!(row.delete().unwrap() == 0);
+}
+
+#[derive(Turbosql, Default, Debug, PartialEq, Clone)]
+struct BatchIntegrationTest {
+ rowid: Option<i64>,
+ name: Option<String>,
+}
+
+#[test]
+fn insert_returns_rowid() {
+ let mut row = BatchIntegrationTest { rowid: None, name: Some("first".to_string()) };
+ let rowid = row.insert().unwrap();
+ assert!(select!(i64 "rowid FROM batchintegrationtest WHERE name = 'first'").unwrap() == rowid);
+
+ row.name = Some("second".to_string());
+ let rowid = row.insert_mut().unwrap();
+ assert!(row.rowid == Some(rowid));
+ assert!(select!(BatchIntegrationTest "WHERE rowid = ?", rowid).unwrap() == row);
+
+ let rows = vec![
+  BatchIntegrationTest { rowid: None, name: Some("third".to_string()) },
+  BatchIntegrationTest { rowid: None, name: Some("fourth".to_string()) },
+ ];
+ let rowids = BatchIntegrationTest::insert_batch(&rows).unwrap();
+ assert!(rowids.len() == 2);
+ assert!(rowids[1] == rowids[0] + 1);
+ assert!(select!(Vec<BatchIntegrationTest>).unwrap().len() == 4);
+}
+
+#[derive(Turbosql, Default, Debug, PartialEq, Clone)]
+struct TransactionIntegrationTest {
+ rowid: Option<i64>,
+ name: Option<String>,
+}
+
+#[test]
+fn transactions() {
+ let count = || select!(i64 "COUNT(*) FROM transactionintegrationtest").unwrap();
+ let row = |name: &str| TransactionIntegrationTest { rowid: None, name: Some(name.to_string()) };
+
+ turbosql::transaction(|| {
+  row("committed").insert()?;
+  execute!("INSERT INTO transactionintegrationtest (name) VALUES (?)", "also committed")
+ })
+ .unwrap();
+ assert!(count() == 2);
+
+ let result: Result<(), turbosql::Error> = turbosql::transaction(|| {
+  row("rolled back").insert()?;
+  Err(turbosql::Error::QueryReturnedNoRows)
+ });
+ assert!(result.is_err());
+ assert!(count() == 2);
+
+ let result = std::panic::catch_unwind(|| {
+  turbosql::transaction(|| -> Result<(), turbosql::Error> {
+   row("panicked").insert()?;
+   panic!("rollback on panic");
+  })
+ });
+ assert!(result.is_err());
+ assert!(count() == 2);
+
+ let tx = turbosql::Transaction::begin().unwrap();
+ row("outer").insert().unwrap();
+ let inner = turbosql::Transaction::begin().unwrap();
+ row("inner").insert().unwrap();
+ inner.rollback().unwrap();
+ assert!(count() == 3);
+ tx.commit().unwrap();
+ assert!(select!(Option<TransactionIntegrationTest> "WHERE name = 'outer'").unwrap().is_some());
+ assert!(select!(Option<TransactionIntegrationTest> "WHERE name = 'inner'").unwrap().is_none());
+}
+
+#[derive(Turbosql, Default, Debug, PartialEq, Clone)]
+struct NotNullIntegrationTest {
+ rowid: Option<i64>,
+ name: String,
+ age: i64,
+ score: f64,
+ active: bool,
+ small: u8,
+ data: Blob,
+ note: Option<String>,
+}
+
+#[test]
+fn not_null_columns() {
+ let row = NotNullIntegrationTest {
+  rowid: None,
+  name: "Cat".to_string(),
+  age: 7,
+  score: 0.5,
+  active: true,
+  small: 255,
+  data: vec![1, 2, 3],
+  note: None,
+ };
+ let rowid = row.insert().unwrap();
+ let row = NotNullIntegrationTest { rowid: Some(rowid), ..row };
+
+ assert!(select!(NotNullIntegrationTest "WHERE rowid = ?", rowid).unwrap() == row);
+ assert!(NotNullIntegrationTest::select_all().unwrap() == vec![row.clone()]);
+ assert!(select!(i64 "age FROM notnullintegrationtest").unwrap() == 7);
+
+ assert!(execute!("INSERT INTO notnullintegrationtest (name) VALUES (NULL)").is_err());
+
+ execute!("INSERT INTO notnullintegrationtest (note) VALUES ('defaults')").unwrap();
+ assert!(
+  select!(NotNullIntegrationTest "WHERE note = 'defaults'").unwrap()
+   == NotNullIntegrationTest {
+    rowid: Some(rowid + 1),
+    note: Some("defaults".to_string()),
+    ..Default::default()
+   }
+ );
+}
+
+#[derive(Default, Debug, PartialEq, Clone, Copy)]
+struct Cents(i64);
+
+impl turbosql::TurbosqlType for Cents {
+ type Affinity = turbosql::affinity::Integer;
+}
+
+impl turbosql::ToSql for Cents {
+ fn to_sql(&self) -> turbosql::Result<turbosql::ToSqlOutput<'_>> {
+  self.0.to_sql()
+ }
+}
+
+impl turbosql::FromSql for Cents {
+ fn column_result(value: turbosql::ValueRef<'_>) -> turbosql::FromSqlResult<Self> {
+  Ok(Cents(value.as_i64()?))
+ }
+}
+
+#[derive(Turbosql, Default, Debug, PartialEq, Clone)]
+struct CustomTypeIntegrationTest {
+ rowid: Option<i64>,
+ name: Option<std::string::String>,
+ data: Option<Vec<u8>>,
+ #[turbosql(sql_type = "INTEGER")]
+ price: Option<Cents>,
+ #[turbosql(sql_type = "INTEGER")]
+ total: Cents,
+}
+
+#[test]
+fn custom_types() {
+ let row = CustomTypeIntegrationTest {
+  rowid: None,
+  name: Some("Widget".to_string()),
+  data: Some(vec![4, 5]),
+  price: Some(Cents(150)),
+  total: Cents(300),
+ };
+ let rowid = row.insert().unwrap();
+ let row = CustomTypeIntegrationTest { rowid: Some(rowid), ..row };
+
+ assert!(select!(CustomTypeIntegrationTest "WHERE rowid = ?", rowid).unwrap() == row);
+ assert!(select!(i64 "price FROM customtypeintegrationtest").unwrap() == 150);
+ assert!(select!(i64 "total FROM customtypeintegrationtest").unwrap() == 300);
+}
+
+#[derive(Default, Debug, PartialEq, Clone, serde::Serialize, serde::Deserialize)]
+struct JsonMeta {
+ score: f64,
+ sources: Vec<String>,
+}
+
+#[derive(Turbosql, Default, Debug, PartialEq, Clone)]
+struct JsonIntegrationTest {
+ rowid: Option<i64>,
+ #[turbosql(json)]
+ tags: Vec<String>,
+ #[turbosql(json)]
+ meta: Option<JsonMeta>,
+}
+
+#[derive(Turbosql, Default, Debug, PartialEq, Clone)]
+struct JsonNullIntegrationTest {
+ rowid: Option<i64>,
+ #[turbosql(json)]
+ unit: (),
+ #[turbosql(json)]
+ tags: Vec<String>,
+ #[turbosql(json)]
+ meta: Option<JsonMeta>,
+}
+
+#[test]
+fn json_columns() {
+ let row = JsonIntegrationTest {
+  rowid: None,
+  tags: vec!["a".to_string(), "b".to_string()],
+  meta: Some(JsonMeta { score: 0.5, sources: vec!["ddg".to_string()] }),
+ };
+ let rowid = row.insert().unwrap();
+ let row = JsonIntegrationTest { rowid: Some(rowid), ..row };
+ let empty = JsonIntegrationTest { tags: vec![], ..Default::default() };
+ let empty_rowid = empty.insert().unwrap();
+
+ assert!(select!(JsonIntegrationTest "WHERE rowid = ?", rowid).unwrap() == row);
+ assert!(JsonIntegrationTest::select_one_where("rowid = ?", [rowid]).unwrap() == row);
+ assert!(
+  select!(bool r#"tags = '["a","b"]' FROM jsonintegrationtest WHERE rowid = ?"#, rowid).unwrap()
+ );
+ assert!(
+  select!(bool "meta IS NULL FROM jsonintegrationtest WHERE rowid = ?", empty_rowid).unwrap()
+ );
+
+ assert!(
+  select!(Vec<JsonIntegrationTest> "WHERE json_extract(meta, '$.sources[0]') = ?", "ddg").unwrap()
+   == vec![row.clone()]
+ );
+
+ let row = JsonIntegrationTest { meta: None, ..row };
+ row.update().unwrap();
+ assert!(select!(JsonIntegrationTest "WHERE rowid = ?", rowid).unwrap().meta.is_none());
+
+ // JSON null is stored as the text `null`, and `None` as SQL `NULL`
+
+ let row = JsonNullIntegrationTest { rowid: None, unit: (), tags: vec![], meta: None };
+ let rowid = row.insert().unwrap();
+ let row = JsonNullIntegrationTest { rowid: Some(rowid), ..row };
+ assert!(select!(JsonNullIntegrationTest "WHERE rowid = ?", rowid).unwrap() == row);
+ assert!(
+  select!(String "unit FROM jsonnullintegrationtest WHERE rowid = ?", rowid).unwrap() == "null"
+ );
+ assert!(select!(bool "meta IS NULL FROM jsonnullintegrationtest WHERE rowid = ?", rowid).unwrap());
+
+ // as in rows from before the column was added, which `Vec` can't hold
+ execute!("UPDATE jsonnullintegrationtest SET tags = 'null' WHERE rowid = ?", rowid).unwrap();
+ let e = JsonNullIntegrationTest::select_one_where("rowid = ?", [rowid]).unwrap_err();
+ assert!(e.to_string().contains("tags"), "{}", e);
+ assert!(JsonNullIntegrationTest::query().all().is_err());
+}
+
+#[derive(Turbosql, Debug, PartialEq, Clone, Copy)]
+enum Knowledge {
+ Name,
+ Size,
+ LocalId,
+}
+
+#[derive(Turbosql, Default, Debug, PartialEq, Clone, Copy)]
+#[turbosql(repr = "int")]
+enum Priority {
+ #[default]
+ Low = 1,
+ High = 10,
+ Urgent,
+}
+
+#[derive(Turbosql, Default, Debug, PartialEq, Clone)]
+struct EnumIntegrationTest {
+ rowid: Option<i64>,
+ kind: Option<Knowledge>,
+ priority: Priority,
+}
+
+#[test]
+fn enum_columns() {
+ let row =
+  EnumIntegrationTest { rowid: None, kind: Some(Knowledge::LocalId), priority: Priority::Urgent };
+ let rowid = row.insert().unwrap();
+ let row = EnumIntegrationTest { rowid: Some(rowid), ..row };
+
+ assert!(select!(EnumIntegrationTest "WHERE rowid = ?", rowid).unwrap() == row);
+ assert!(select!(bool "kind = 'LocalId' AND priority = 11 FROM enumintegrationtest WHERE rowid = ?", rowid).unwrap());
+ assert!(select!(Vec<EnumIntegrationTest> "WHERE kind = ?", Knowledge::Size).unwrap().is_empty());
+
+ // migrations.toml added these columns before they had a CHECK, so the table was rebuilt with it
+ assert!(execute!("INSERT INTO enumintegrationtest (kind) VALUES ('Colour')").is_err());
+ assert!(execute!("INSERT INTO enumintegrationtest (priority) VALUES (2)").is_err());
+
+ execute!("INSERT INTO enumintegrationtest (kind) VALUES (NULL)").unwrap();
+ assert!(
+  select!(EnumIntegrationTest "WHERE rowid = ?", rowid + 1).unwrap()
+   == EnumIntegrationTest { rowid: Some(rowid + 1), kind: None, priority: Priority::Low }
+ );
+
+ use turbosql::FromSql;
+ assert!(Knowledge::column_result(turbosql::ValueRef::Text(b"Colour")).is_err());
+ assert!(Priority::column_result(turbosql::ValueRef::Integer(2)).is_err());
+}
+
+#[derive(Turbosql, Default, Debug, PartialEq, Clone)]
+#[turbosql(index(cachekey, startbytepos, endbytepos))]
+struct IndexIntegrationTest {
+ rowid: Option<i64>,
+ #[turbosql(index)]
+ url: Option<String>,
+ #[turbosql(unique)]
+ host: Option<String>,
+ cachekey: Option<String>,
+ startbytepos: Option<i64>,
+ endbytepos: Option<i64>,
+}
+
+#[test]
+fn indexes() {
+ let plan = |sql: &str| -> String {
+  turbosql::__with_db(|db| {
+   db
+    .prepare(&format!("EXPLAIN QUERY PLAN {}", sql))
+    .unwrap()
+    .query_map(turbosql::params![], |row| row.get::<_, String>(3))
+    .unwrap()
+    .map(|r| r.unwrap())
+    .collect::<Vec<_>>()
+    .join("\n")
+  })
+ };
+
+ assert!(plan("SELECT * FROM indexintegrationtest WHERE url = 'a'")
+  .contains("USING INDEX indexintegrationtest_url_index"));
+ assert!(plan(
+  "SELECT * FROM indexintegrationtest WHERE cachekey = 'a' AND startbytepos = 0 AND endbytepos = 1"
+ )
+ .contains("USING INDEX indexintegrationtest_cachekey_startbytepos_endbytepos_index"));
+
+ IndexIntegrationTest { host: Some("example.com".into()), ..Default::default() }.insert().unwrap();
+ assert!(IndexIntegrationTest { host: Some("example.com".into()), ..Default::default() }
+  .insert()
+  .is_err());
+}
+
+#[derive(Turbosql, Default, Debug, PartialEq, Clone)]
+struct ReferencesParentIntegrationTest {
+ rowid: Option<i64>,
+ #[turbosql(unique)]
+ name: Option<String>,
+}
+
+#[derive(Turbosql, Default, Debug, PartialEq, Clone)]
+struct ReferencesChildIntegrationTest {
+ rowid: Option<i64>,
+ #[turbosql(references = "ReferencesParentIntegrationTest", on_delete = "cascade")]
+ parent_id: Option<i64>,
+ #[turbosql(references = "ReferencesParentIntegrationTest.rowid", on_delete = "set null")]
+ other: Option<i64>,
+}
+
+#[derive(Turbosql, Default, Debug, PartialEq, Clone)]
+struct ReferencesByNameIntegrationTest {
+ rowid: Option<i64>,
+ #[turbosql(references = "ReferencesParentIntegrationTest.name", on_delete = "cascade")]
+ parent_name: Option<String>,
+}
+
+#[test]
+fn references() {
+ let mut parent =
+  ReferencesParentIntegrationTest { name: Some("parent".into()), ..Default::default() };
+ parent.insert_mut().unwrap();
+
+ let mut child = ReferencesChildIntegrationTest { parent_id: parent.rowid, ..Default::default() };
+ child.insert_mut().unwrap();
+ let orphan = ReferencesChildIntegrationTest { rowid: None, parent_id: None, other: parent.rowid };
+ let orphan_rowid = orphan.insert().unwrap();
+
+ assert!(child.parent().unwrap() == Some(parent.clone()));
+ assert!(child.referencesparentintegrationtest().unwrap().is_none());
+ assert!(parent.referenceschildintegrationtests_by_parent_id().unwrap() == vec![child.clone()]);
+ assert!(parent.referenceschildintegrationtests_by_other().unwrap().len() == 1);
+
+ let dangling = ReferencesChildIntegrationTest {
+  parent_id: Some(parent.rowid.unwrap() + 100),
+  ..Default::default()
+ };
+ assert!(dangling.insert().is_err());
+
+ let by_name = ReferencesByNameIntegrationTest { rowid: None, parent_name: parent.name.clone() };
+ by_name.insert().unwrap();
+ assert!(by_name.referencesparentintegrationtest().unwrap() == Some(parent.clone()));
+ assert!(parent.referencesbynameintegrationtests().unwrap().len() == 1);
+ assert!(ReferencesByNameIntegrationTest { rowid: None, parent_name: Some("nobody".into()) }
+  .insert()
+  .is_err());
+
+ parent.delete().unwrap();
+ assert!(select!(Option<ReferencesChildIntegrationTest> "WHERE rowid = ?", child.rowid)
+  .unwrap()
+  .is_none());
+ assert!(
+  select!(ReferencesChildIntegrationTest "WHERE rowid = ?", orphan_rowid).unwrap()
+   == ReferencesChildIntegrationTest { rowid: Some(orphan_rowid), parent_id: None, other: None }
+ );
+}
+
+#[derive(Turbosql, Default, Debug, PartialEq, Clone)]
+#[turbosql(drop_column = "legacy")]
+struct RenameDropIntegrationTest {
+ rowid: Option<i64>,
+ #[turbosql(renamed_from = "old_name")]
+ new_name: Option<String>,
+ #[turbosql(index)]
+ keep: Option<i64>,
+}
+
+#[test]
+fn rename_and_drop_columns() {
+ let columns = turbosql::__with_db(|db| {
+  db
+   .prepare("SELECT name FROM pragma_table_info('renamedropintegrationtest')")
+   .unwrap()
+   .query_map(turbosql::params![], |row| row.get::<_, String>(0))
+   .unwrap()
+   .map(|r| r.unwrap())
+   .collect::<Vec<_>>()
+ });
+ assert!(columns == vec!["rowid", "new_name", "keep"]);
+ assert!(select!(bool "COUNT(*) = 1 FROM sqlite_master WHERE name = 'renamedropintegrationtest_keep_index'").unwrap());
+
+ let row =
+  RenameDropIntegrationTest { rowid: None, new_name: Some("renamed".into()), keep: Some(1) };
+ let rowid = row.insert().unwrap();
+ assert!(
+  select!(RenameDropIntegrationTest "WHERE new_name = 'renamed'").unwrap()
+   == RenameDropIntegrationTest { rowid: Some(rowid), ..row }
+ );
+}
+
+// migrations.toml already renamed `name` to `title` and dropped `readded`
+#[derive(Turbosql, Default, Debug, PartialEq, Clone)]
+struct RemigrateIntegrationTest {
+ rowid: Option<i64>,
+ #[turbosql(renamed_from = "title")]
+ name: Option<String>,
+ readded: Option<String>,
+}
+
+#[test]
+fn rename_back_and_readd_columns() {
+ let columns = turbosql::__with_db(|db| {
+  db
+   .prepare("SELECT name FROM pragma_table_info('remigrateintegrationtest')")
+   .unwrap()
+   .query_map(turbosql::params![], |row| row.get::<_, String>(0))
+   .unwrap()
+   .map(|r| r.unwrap())
+   .collect::<Vec<_>>()
+ });
+ assert!(columns == vec!["rowid", "name", "readded"]);
+ assert!(
+  select!(i64 "COUNT(*) FROM turbosql_migrations WHERE migration = 'ALTER TABLE remigrateintegrationtest ADD COLUMN readded TEXT'")
+   .unwrap()
+   == 2
+ );
+
+ let row = RemigrateIntegrationTest {
+  rowid: None,
+  name: Some("name".into()),
+  readded: Some("readded".into()),
+ };
+ let rowid = row.insert().unwrap();
+ assert!(
+  select!(RemigrateIntegrationTest "WHERE rowid = ?", rowid).unwrap()
+   == RemigrateIntegrationTest { rowid: Some(rowid), ..row }
+ );
+}
+
+#[derive(Turbosql, Default, Debug, PartialEq, Clone)]
+struct SchemaIntegrationTest {
+ rowid: Option<i64>,
+ name: Option<String>,
+}
+
+#[test]
+fn verify_schema() {
+ assert!(turbosql::verify_schema() == Ok(()));
+
+ turbosql::transaction(|| {
+  // SQLite's own tables, and those no migration mentions, aren't checked
+  execute!("ANALYZE").unwrap();
+  execute!("CREATE TABLE schemaintegrationtest_extra (rowid INTEGER PRIMARY KEY)").unwrap();
+  assert!(turbosql::verify_schema() == Ok(()));
+
+  execute!("ALTER TABLE schemaintegrationtest ADD COLUMN extra INTEGER NOT NULL DEFAULT 1")
+   .unwrap();
+
+  let mismatch = turbosql::verify_schema().unwrap_err();
+  assert!(mismatch.unexpected_tables.is_empty());
+  assert!(mismatch.tables.len() == 1);
+  assert!(mismatch.tables[0].table == "schemaintegrationtest");
+  assert!(mismatch.tables[0].unexpected_columns == vec!["extra"]);
+  assert!(mismatch.to_string().contains("unexpected column extra"));
+
+  Err::<(), _>(turbosql::Error::QueryReturnedNoRows)
+ })
+ .unwrap_err();
+
+ assert!(turbosql::verify_schema() == Ok(()));
+}
+
+#[test]
+fn open_errors() {
+ assert!(select!(i64 "1").unwrap() == 1);
+ assert!(matches!(turbosql::open(Default::default()), Err(turbosql::TurbosqlError::AlreadyOpen)));
+ assert!(turbosql::set_db_path(std::path::Path::new("elsewhere.sqlite")).is_err());
+}
+
+#[derive(Turbosql, Default, Debug, PartialEq, Clone)]
+struct DatabaseIntegrationTest {
+ rowid: Option<i64>,
+ name: Option<String>,
+}
+
+#[derive(Turbosql, Default, Debug, PartialEq, Clone)]
+#[turbosql(database = "cache")]
+struct CacheIntegrationTest {
+ rowid: Option<i64>,
+ key: Option<String>,
+}
+
+#[test]
+fn databases() {
+ use turbosql::{Config, Database};
+
+ let db = Database::open(Config { path: Some(":memory:".into()), ..Default::default() }).unwrap();
+
+ execute!(db, "INSERT INTO databaseintegrationtest (name) VALUES (?)", "isolated").unwrap();
+ assert!(select!(db, Vec<DatabaseIntegrationTest>).unwrap().len() == 1);
+ assert!(select!(Vec<DatabaseIntegrationTest>).unwrap().is_empty());
+
+ let result: Result<(), turbosql::Error> = db.transaction(|| {
+  execute!(&db, "DELETE FROM databaseintegrationtest").unwrap();
+  assert!(select!(Vec<DatabaseIntegrationTest>).unwrap().is_empty());
+  Err(turbosql::Error::QueryReturnedNoRows)
+ });
+ assert!(result.is_err());
+ assert!(select!(db.clone(), i64 "COUNT(*) FROM databaseintegrationtest").unwrap() == 1);
+
+ // #[turbosql(database = "cache")] structs live in their own database
+
+ CacheIntegrationTest { rowid: None, key: Some("k".into()) }.insert().unwrap();
+ assert!(select!(Vec<CacheIntegrationTest> "WHERE key = ?", "k").unwrap().len() == 1);
+ assert!(select!(i64 "COUNT(*) FROM cacheintegrationtest").unwrap() == 0);
+
+ let cache = turbosql::named_database("cache");
+ assert!(select!(cache, i64 "COUNT(*) FROM cacheintegrationtest").unwrap() == 1);
+ assert!(matches!(
+  turbosql::open_named("cache", Default::default()),
+  Err(turbosql::TurbosqlError::AlreadyOpen)
+ ));
+}
+
+#[test]
+fn reader_pool() {
+ use turbosql::{Config, Database};
+
+ let path =
+  std::env::temp_dir().join(format!("turbosql_reader_pool_{}.sqlite", std::process::id()));
+ let db =
+  Database::open(Config { path: Some(path.clone()), readers: Some(2), ..Default::default() })
+   .unwrap();
+
+ execute!(db, "INSERT INTO databaseintegrationtest (name) VALUES (?)", "committed").unwrap();
+
+ // While this thread has a write transaction open, other threads still read the last commit
+
+ let tx = db.begin().unwrap();
+ execute!(db, "INSERT INTO databaseintegrationtest (name) VALUES (?)", "uncommitted").unwrap();
+ assert!(select!(db, i64 "COUNT(*) FROM databaseintegrationtest").unwrap() == 2);
+
+ let readers = (0..4)
+  .map(|_| {
+   let db = db.clone();
+   std::thread::spawn(move || select!(db, i64 "COUNT(*) FROM databaseintegrationtest").unwrap())
+  })
+  .collect::<Vec<_>>();
+ assert!(readers.into_iter().all(|reader| reader.join().unwrap() == 1));
+
+ tx.commit().unwrap();
+ assert!(select!(db, Vec<DatabaseIntegrationTest>).unwrap().len() == 2);
+
+ drop(db);
+ for suffix in &["", "-wal", "-shm"] {
+  std::fs::remove_file(format!("{}{}", path.display(), suffix)).ok();
+ }
+}
+
+#[derive(Turbosql, Default, Debug, PartialEq, Clone)]
+struct AsyncIntegrationTest {
+ rowid: Option<i64>,
+ name: Option<String>,
+}
+
+/// Minimal executor, so the async test doesn't need tokio
+fn block_on<F: std::future::Future>(future: F) -> F::Output {
+ use std::sync::Arc;
+ use std::task::{Context, Poll, Wake};
+
+ struct ThreadWaker(std::thread::Thread);
+
+ impl Wake for ThreadWaker {
+  fn wake(self: Arc<Self>) {
+   self.0.unpark();
+  }
+ }
+
+ let waker = Arc::new(ThreadWaker(std::thread::current())).into();
+ let mut cx = Context::from_waker(&waker);
+ let mut future = Box::pin(future);
+
+ loop {
+  match future.as_mut().poll(&mut cx) {
+   Poll::Ready(output) => return output,
+   Poll::Pending => std::thread::park(),
+  }
+ }
+}
+
+#[test]
+fn async_api() {
+ use turbosql::{execute_async, select_async};
+
+ block_on(async {
+  let rowid =
+   AsyncIntegrationTest { rowid: None, name: Some("async".into()) }.insert_async().await.unwrap();
+
+  // parameters are copied before the query is sent to the thread pool
+
+  let name = String::from("async");
+  let task = select_async!(Vec<AsyncIntegrationTest> "WHERE name = ?", &name);
+  drop(name);
+  let rows = task.await.unwrap();
+  assert!(rows == vec![AsyncIntegrationTest { rowid: Some(rowid), name: Some("async".into()) }]);
+
+  execute_async!("UPDATE asyncintegrationtest SET name = ? WHERE rowid = ?", "renamed", rowid)
+   .await
+   .unwrap();
+  let row = select_async!(AsyncIntegrationTest "WHERE rowid = ?", rowid).await.unwrap();
+  assert!(row.name == Some("renamed".into()));
+
+  assert!(row.delete_async().await.unwrap() == 1);
+  assert!(select_async!(i64 "COUNT(*) FROM asyncintegrationtest").await.unwrap() == 0);
+ });
+
+ let panicked = std::panic::catch_unwind(|| block_on(turbosql::spawn_blocking(|| panic!("boom"))));
+ assert!(panicked.is_err());
+}
+
+#[derive(Turbosql, Default, Debug, PartialEq, Clone)]
+struct IterIntegrationTest {
+ rowid: Option<i64>,
+ n: Option<i64>,
+}
+
+#[test]
+fn iterators() {
+ use turbosql::{Config, Database};
+
+ let rows = (0..100).map(|n| IterIntegrationTest { rowid: None, n: Some(n) }).collect::<Vec<_>>();
+ IterIntegrationTest::insert_batch(&rows).unwrap();
+
+ let iter = select!(Iter<IterIntegrationTest> "ORDER BY n").unwrap();
+ assert!(iter.map(|row| row.unwrap().n.unwrap()).eq(0..100));
+
+ // On a database with readers, rows stream from another connection
+
+ let path = std::env::temp_dir().join(format!("turbosql_iterators_{}.sqlite", std::process::id()));
+ let db = Database::open(Config { path: Some(path.clone()), ..Default::default() }).unwrap();
+ db
+  .transaction(|| {
+   (0..1000).try_for_each(|n| {
+    execute!(db, "INSERT INTO iterintegrationtest (n) VALUES (?)", n)?;
+    Ok::<_, turbosql::Error>(())
+   })
+  })
+  .unwrap();
+
+ let mut iter = select!(db, Iter<IterIntegrationTest> "WHERE n >= ? ORDER BY n", 10).unwrap();
+ assert!(iter.next().unwrap().unwrap().n == Some(10));
+ execute!(db, "DELETE FROM iterintegrationtest").unwrap();
+ assert!(iter.count() == 989);
+
+ // Iterators hold a reader each; other reads use the writer meanwhile, and another iterator
+ // waits for a reader
+
+ execute!(db, "INSERT INTO iterintegrationtest (n) VALUES (1), (2)").unwrap();
+ let iters = (0..4).map(|_| select!(db, Iter<IterIntegrationTest>).unwrap()).collect::<Vec<_>>();
+ assert!(select!(db, i64 "COUNT(*) FROM iterintegrationtest").unwrap() == 2);
+ let waiting = {
+  let db = db.clone();
+  std::thread::spawn(move || select!(db, Iter<IterIntegrationTest>).unwrap().count())
+ };
+ assert!(iters.into_iter().all(|iter| iter.count() == 2));
+ assert!(waiting.join().unwrap() == 2);
+
+ // Inside a transaction, rows stream from the transaction's connection, which it gets back once
+ // the iterator is exhausted or dropped
+
+ db
+  .transaction(|| {
+   execute!(db, "INSERT INTO iterintegrationtest (n) VALUES (3)")?;
+   assert!(select!(db, Iter<IterIntegrationTest>)?.count() == 3);
+   let mut iter = select!(db, Iter<IterIntegrationTest> "ORDER BY n")?;
+   assert!(iter.next().unwrap()?.n == Some(1));
+   drop(iter);
+   execute!(db, "DELETE FROM iterintegrationtest WHERE n = 3")
+  })
+  .unwrap();
+
+ // A panic decoding a streamed row reaches the consumer, rather than ending the rows early
+
+ let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+  let iter =
+   db.__iter("SELECT n FROM iterintegrationtest", Ok(Vec::new()), |_| -> turbosql::Result<i64> {
+    panic!("decoding row")
+   });
+  iter.unwrap().count()
+ }));
+ assert!(panicked.is_err());
+
+ drop(db);
+ for suffix in &["", "-wal", "-shm"] {
+  std::fs::remove_file(format!("{}{}", path.display(), suffix)).ok();
+ }
+
+ // Rows that fail to decode are errors, rather than skipped
+
+ execute!("INSERT INTO iterintegrationtest (n) VALUES ('not a number')").unwrap();
+ assert!(select!(Vec<IterIntegrationTest>).is_err());
+ assert!(select!(Iter<IterIntegrationTest>).unwrap().any(|row| row.is_err()));
+}
+
+#[derive(Turbosql, Default, Debug, PartialEq, Clone)]
+struct BlobIntegrationTest {
+ rowid: Option<i64>,
+ bytes: Option<Blob>,
+}
+
+#[test]
+fn blob_handles() {
+ use std::io::{Read, Seek, SeekFrom, Write};
+
+ let bytes = (0..100_000).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+ let mut row = BlobIntegrationTest { rowid: None, bytes: Some(bytes.clone()) };
+ row.insert_mut().unwrap();
+
+ let mut blob = row.bytes_blob().unwrap();
+ assert!(blob.len() == 100_000);
+
+ let mut buf = vec![0; 10];
+ blob.seek(SeekFrom::Start(50_000)).unwrap();
+ blob.read_exact(&mut buf).unwrap();
+ assert!(buf == bytes[50_000..50_010]);
+
+ blob.seek(SeekFrom::End(-3)).unwrap();
+ blob.write_all(b"end").unwrap();
+ assert!(blob.write(b"!").unwrap() == 0);
+ assert!(blob.seek(SeekFrom::Current(-100_001)).is_err());
+
+ let mut all = Vec::new();
+ blob.seek(SeekFrom::Start(0)).unwrap();
+ blob.read_to_end(&mut all).unwrap();
+ assert!(all[..99_997] == bytes[..99_997] && &all[99_997..] == b"end");
+
+ let stored = select!(BlobIntegrationTest "WHERE rowid = ?", row.rowid).unwrap();
+ assert!(stored.bytes == Some(all));
+}
+
+#[derive(Turbosql, Default, Debug, PartialEq, Clone)]
+struct TupleIntegrationTest {
+ rowid: Option<i64>,
+ url: Option<String>,
+ affection: Option<i64>,
+ score: f64,
+}
+
+#[test]
+fn tuples_and_anonymous_structs() {
+ TupleIntegrationTest { rowid: None, url: Some("a.com".into()), affection: Some(1), score: 0.5 }
+  .insert()
+  .unwrap();
+ TupleIntegrationTest { rowid: None, url: Some("b.com".into()), affection: None, score: 2.0 }
+  .insert()
+  .unwrap();
+
+ assert!(
+  select!(Vec<(String, Option<i64>)> "url, affection FROM tupleintegrationtest ORDER BY url")
+   .unwrap()
+   == vec![("a.com".to_string(), Some(1)), ("b.com".to_string(), None)]
+ );
+ assert!(
+  select!((String, f64) "url, score FROM tupleintegrationtest WHERE url = ?", "b.com").unwrap()
+   == ("b.com".to_string(), 2.0)
+ );
+ assert!(
+  select!(Option<(i64,)> "affection FROM tupleintegrationtest WHERE url = ?", "a.com").unwrap()
+   == Some((1,))
+ );
+
+ assert!(select!(f64 "SUM(score) FROM tupleintegrationtest").unwrap() == 2.5);
+ assert!(select!(Option<String> "url FROM tupleintegrationtest WHERE score > 5")
+  .unwrap()
+  .is_none());
+ assert!(
+  select!(Vec<Option<i64>> "affection FROM tupleintegrationtest ORDER BY url").unwrap()
+   == vec![Some(1), None]
+ );
+ assert!(
+  select!(String "url FROM tupleintegrationtest WHERE affection IS NULL").unwrap() == "b.com"
+ );
+
+ let result =
+  select!("url AS url_String, score AS score_f64 FROM tupleintegrationtest WHERE url = ?", "a.com")
+   .unwrap();
+ assert!(result.url == "a.com" && result.score == 0.5);
+
+ let results =
+  select!(Vec<_> "url AS url_String, score > 1 AS big_bool FROM tupleintegrationtest ORDER BY url")
+   .unwrap();
+ assert!(results.iter().map(|r| r.big).collect::<Vec<_>>() == vec![false, true]);
+ assert!(results[1].url == "b.com");
+}
+
+#[derive(Turbosql, Default, Debug, PartialEq, Clone)]
+struct ParamIntegrationTest {
+ rowid: Option<i64>,
+ name: Option<String>,
+ score: Option<f64>,
+ active: Option<bool>,
+}
+
+#[test]
+fn parameter_types() {
+ // each parameter's type is checked against its column at compile time, e.g. an i32 for
+ // `name = ?` or a String for `LIMIT ?` doesn't compile
+
+ execute!("INSERT INTO paramintegrationtest (name, score, active) VALUES (?, ?, ?)", "a", 1, true)
+  .unwrap();
+ execute!("INSERT INTO paramintegrationtest (name, score, active) VALUES (?, ?, ?)", "b", 2.5, 0)
+  .unwrap();
+ execute!("UPDATE paramintegrationtest SET score = ? WHERE name = ?", Some(1.5), &"a".to_string())
+  .unwrap();
+
+ assert!(
+  select!(Vec<String> "name FROM paramintegrationtest p WHERE p.score BETWEEN ? AND ? ORDER BY name LIMIT ?, ?", 1, 3.0, 0, 2)
+   .unwrap()
+   == vec!["a", "b"]
+ );
+ assert!(
+  select!(Vec<String> "name FROM paramintegrationtest WHERE ? = active AND name NOT IN (?, ?)", true, "b", "c")
+   .unwrap()
+   == vec!["a"]
+ );
+ assert!(
+  select!(f64 "score FROM paramintegrationtest WHERE name LIKE ? || '%'", String::from("b"))
+   .unwrap()
+   == 2.5
+ );
+
+ // other ToSql types are checked by what they hold; runtime-typed values fit any column
+
+ let (cow, rc, arc) = (
+  std::borrow::Cow::Borrowed("a"),
+  std::rc::Rc::<str>::from("a"),
+  std::sync::Arc::<str>::from("b"),
+ );
+ assert!(
+  select!(i64 "COUNT(*) FROM paramintegrationtest WHERE name IN (?, ?, ?)", cow, rc, arc).unwrap()
+   == 2
+ );
+ assert!(
+  select!(i64 "COUNT(*) FROM paramintegrationtest WHERE name = ? OR score = ? OR active = ?", turbosql::Value::Integer(1), turbosql::Null, turbosql::Value::Text("x".into()))
+   .unwrap()
+   == 0
+ );
+}
+
+#[derive(Turbosql, Default, Debug, PartialEq, Clone)]
+struct NamedIntegrationTest {
+ rowid: Option<i64>,
+ query: Option<String>,
+ url: Option<String>,
+ pos: Option<i64>,
+}
+
+#[test]
+fn named_parameters() {
+ let query = "rust".to_string();
+ let row = NamedIntegrationTest {
+  rowid: None,
+  query: Some(query.clone()),
+  url: Some("a.com".into()),
+  pos: Some(1),
+ };
+
+ execute!("INSERT INTO namedintegrationtest (query, url, pos) VALUES (:query, #{row.url}, #{row.pos.unwrap() + 1})").unwrap();
+ execute!(
+  "INSERT INTO namedintegrationtest (query, url, pos) VALUES (:query, ?, :pos)",
+  "b.com",
+  pos = 3
+ )
+ .unwrap();
+
+ assert!(
+  select!(Vec<String> "url FROM namedintegrationtest WHERE query = :query AND pos >= :min ORDER BY pos", min = 0)
+   .unwrap()
+   == vec!["a.com", "b.com"]
+ );
+ assert!(
+  select!(i64 "COUNT(*) FROM namedintegrationtest WHERE url = :url OR (query = :query AND url = ?)", "b.com", url = "a.com")
+   .unwrap()
+   == 2
+ );
+ assert!(
+  select!(String "':query' || '#{nope}' -- :not_a_param\n || url FROM namedintegrationtest WHERE pos = #{1 + 1}")
+   .unwrap()
+   == ":query#{nope}a.com"
+ );
+ assert!(
+  select!(NamedIntegrationTest "WHERE pos = :pos", pos = 3).unwrap().url == Some("b.com".into())
+ );
+
+ // members can share their names with derived fns, such as query()
+ assert!(NamedIntegrationTest::columns().query.name() == "query");
+ assert!(NamedIntegrationTest::query().count().unwrap() == 2);
+}
+
+#[derive(Turbosql, Default, Debug, PartialEq, Clone)]
+struct QueryIntegrationTest {
+ rowid: Option<i64>,
+ name: Option<String>,
+ size: Option<i64>,
+}
+
+#[test]
+fn query_builder() {
+ for (name, size) in
+  &[("a.mp4", Some(10)), ("b.mp4", Some(30)), ("c.txt", Some(20)), ("d.mp4", None)]
+ {
+  QueryIntegrationTest { rowid: None, name: Some(name.to_string()), size: *size }.insert().unwrap();
+ }
+
+ let names =
+  |rows: Vec<QueryIntegrationTest>| rows.into_iter().map(|r| r.name.unwrap()).collect::<Vec<_>>();
+
+ let c = QueryIntegrationTest::columns();
+ let videos = QueryIntegrationTest::query().filter(c.name.like("%.mp4"));
+ assert!(videos.count().unwrap() == 3);
+ assert!(
+  names(videos.clone().order_by(c.size.desc()).all().unwrap()) == ["b.mp4", "a.mp4", "d.mp4"]
+ );
+ assert!(names(videos.clone().filter(c.size.gt(15)).all().unwrap()) == ["b.mp4"]);
+ assert!(names(videos.clone().filter(!c.size.is_not_null()).all().unwrap()) == ["d.mp4"]);
+
+ let sorted = QueryIntegrationTest::query().order_by(c.name);
+ assert!(names(sorted.clone().limit(2).offset(1).all().unwrap()) == ["b.mp4", "c.txt"]);
+ assert!(names(sorted.clone().offset(3).all().unwrap()) == ["d.mp4"]);
+ assert!(sorted.clone().limit(1).count().unwrap() == 4);
+ assert!(sorted.first().unwrap().unwrap().name == Some("a.mp4".into()));
+ assert!(
+  sorted.iter().unwrap().map(|r| r.unwrap().size).collect::<Vec<_>>()
+   == [Some(10), Some(30), Some(20), None]
+ );
+
+ let either = c.size.is_in(vec![10, 20]).or(c.size.is_null());
+ assert!(
+  names(QueryIntegrationTest::query().filter(either).order_by(c.name).all().unwrap())
+   == ["a.mp4", "c.txt", "d.mp4"]
+ );
+ assert!(QueryIntegrationTest::query()
+  .filter(c.size.is_in(Vec::<i64>::new()))
+  .first()
+  .unwrap()
+  .is_none());
+ assert!(
+  QueryIntegrationTest::query().filter(c.size.ge(20)).order_by(c.size).sql()
+   == "SELECT rowid, name, size FROM queryintegrationtest WHERE size >= ? ORDER BY size ASC"
+ );
+}
+
+#[derive(Turbosql, Default, Debug, PartialEq, Clone)]
+struct FallibleIntegrationTest {
+ rowid: Option<i64>,
+ size: Option<i64>,
+ #[turbosql(json)]
+ tags: Vec<String>,
+}
+
+#[test]
+fn fallible_selects() {
+ let row = FallibleIntegrationTest { rowid: None, size: Some(1), tags: vec!["a".to_string()] };
+ let rowid = row.insert().unwrap();
+ let row = FallibleIntegrationTest { rowid: Some(rowid), ..row };
+
+ assert!(FallibleIntegrationTest::select_all().unwrap() == vec![row.clone()]);
+ assert!(FallibleIntegrationTest::select_where("size = ?", [1]).unwrap() == vec![row.clone()]);
+ assert!(
+  FallibleIntegrationTest::select_sql(
+   "SELECT rowid, size, tags FROM fallibleintegrationtest",
+   turbosql::params![]
+  )
+  .unwrap()
+   == vec![row]
+ );
+ assert!(FallibleIntegrationTest::select_where("size = = ?", [1]).is_err());
+
+ execute!("UPDATE fallibleintegrationtest SET size = 'big'").unwrap();
+ let e = FallibleIntegrationTest::select_all().unwrap_err();
+ assert!(e.to_string().contains("size"), "{}", e);
+
+ execute!("UPDATE fallibleintegrationtest SET size = 1, tags = 'not json'").unwrap();
+ let e = FallibleIntegrationTest::select_one_where("rowid = ?", [rowid]).unwrap_err();
+ assert!(e.to_string().contains("Invalid value in column tags"), "{}", e);
+ assert!(FallibleIntegrationTest::query().all().is_err());
+}
+
+#[derive(Turbosql, Default, Debug, PartialEq, Clone)]
+struct PageIntegrationTest {
+ rowid: Option<i64>,
+ name: Option<String>,
+ timestamp: Option<i64>,
+}
+
+#[test]
+fn pages() {
+ for (name, timestamp) in &[("a", 1), ("b", 2), ("c", 2), ("d", 3), ("e", 4)] {
+  PageIntegrationTest { rowid: None, name: Some(name.to_string()), timestamp: Some(*timestamp) }
+   .insert()
+   .unwrap();
+ }
+
+ // keyset: later pages continue after the last row, even if rows are added before it
+
+ let page =
+  select!(Page<PageIntegrationTest> "ORDER BY timestamp DESC, rowid", None::<&str>, 2).unwrap();
+ assert!(page.items.iter().map(|r| r.name.clone().unwrap()).collect::<Vec<_>>() == ["e", "d"]);
+
+ PageIntegrationTest { rowid: None, name: Some("f".into()), timestamp: Some(5) }.insert().unwrap();
+
+ let page =
+  select!(Page<PageIntegrationTest> "ORDER BY timestamp DESC, rowid", page.next_cursor, 2).unwrap();
+ assert!(page.items.iter().map(|r| r.name.clone().unwrap()).collect::<Vec<_>>() == ["b", "c"]);
+ let page =
+  select!(Page<PageIntegrationTest> "ORDER BY timestamp DESC, rowid", page.next_cursor, 2).unwrap();
+ assert!(page.items.iter().map(|r| r.name.clone().unwrap()).collect::<Vec<_>>() == ["a"]);
+ assert!(page.next_cursor.is_none());
+
+ // any result kind, with parameters before the cursor and page size
+
+ let mut cursor = None;
+ let mut names = Vec::new();
+ loop {
+  let page: Page<(String, i64)> = select!(Page<(String, i64)> "name, timestamp FROM pageintegrationtest WHERE timestamp >= :min ORDER BY name", cursor, 4, min = 2).unwrap();
+  names.extend(page.items.into_iter().map(|(name, _)| name));
+  cursor = match page.next_cursor {
+   Some(next_cursor) => Some(next_cursor),
+   None => break,
+  };
+ }
+ assert!(names == ["b", "c", "d", "e", "f"]);
+
+ // LIMIT/OFFSET, when ORDER BY isn't plain result columns
+
+ let page = select!(Page<String> "name FROM pageintegrationtest ORDER BY -timestamp, name", None::<String>, 3).unwrap();
+ assert!(page.items == ["f", "e", "d"]);
+ let offset_cursor = page.next_cursor.unwrap();
+ let page = select!(Page<String> "name FROM pageintegrationtest ORDER BY -timestamp, name", Some(&offset_cursor), 3).unwrap();
+ assert!(page.items == ["b", "c", "a"] && page.next_cursor.is_none());
+
+ assert!(
+  select!(Page<PageIntegrationTest> "ORDER BY timestamp DESC, rowid", Some("nope"), 2).is_err()
+ );
+ assert!(
+  select!(Page<PageIntegrationTest> "ORDER BY timestamp DESC, rowid", Some(offset_cursor), 2)
+   .is_err()
+ );
+
+ let page = block_on(turbosql::select_async!(Page<String> "name FROM pageintegrationtest ORDER BY name", None::<String>, 10)).unwrap();
+ assert!(page.items.len() == 6 && page.next_cursor.is_none());
+
+ // NULL keys sort first, or last with DESC, and pages of 1 cross every boundary
+
+ PageIntegrationTest { rowid: None, name: Some("g".into()), timestamp: None }.insert().unwrap();
+ PageIntegrationTest { rowid: None, name: Some("h".into()), timestamp: None }.insert().unwrap();
+
+ let (mut cursor, mut names) = (None, Vec::new());
+ loop {
+  let page = select!(Page<PageIntegrationTest> "ORDER BY timestamp, rowid", cursor, 1).unwrap();
+  names.extend(page.items.into_iter().map(|r| r.name.unwrap()));
+  cursor = match page.next_cursor {
+   Some(next_cursor) => Some(next_cursor),
+   None => break,
+  };
+ }
+ assert!(names == ["g", "h", "a", "b", "c", "d", "e", "f"]);
+
+ let (mut cursor, mut names) = (None, Vec::new());
+ loop {
+  let page =
+   select!(Page<PageIntegrationTest> "ORDER BY timestamp DESC, rowid", cursor, 1).unwrap();
+  names.extend(page.items.into_iter().map(|r| r.name.unwrap()));
+  cursor = match page.next_cursor {
+   Some(next_cursor) => Some(next_cursor),
+   None => break,
+  };
+ }
+ assert!(names == ["f", "e", "d", "b", "c", "a", "g", "h"]);
+
+ // rowid breaks ties between b and c when ORDER BY doesn't end with it, whether it's selected
+ // or not
+
+ let (mut cursor, mut names) = (None, Vec::new());
+ loop {
+  let page = select!(Page<PageIntegrationTest> "ORDER BY timestamp DESC", cursor, 1).unwrap();
+  names.extend(page.items.into_iter().map(|r| r.name.unwrap()));
+  cursor = match page.next_cursor {
+   Some(next_cursor) => Some(next_cursor),
+   None => break,
+  };
+ }
+ assert!(names == ["f", "e", "d", "b", "c", "a", "g", "h"]);
+
+ let (mut cursor, mut names) = (None, Vec::new());
+ loop {
+  let page = select!(Page<(String, Option<i64>)> "name, timestamp FROM pageintegrationtest ORDER BY timestamp", cursor, 1).unwrap();
+  names.extend(page.items.into_iter().map(|(name, _)| name));
+  cursor = match page.next_cursor {
+   Some(next_cursor) => Some(next_cursor),
+   None => break,
+  };
+ }
+ assert!(names == ["g", "h", "a", "b", "c", "d", "e", "f"]);
+
+ let (mut cursor, mut timestamps) = (None, Vec::new());
+ loop {
+  let page = select!(Page<Option<i64>> "DISTINCT timestamp FROM pageintegrationtest ORDER BY timestamp", cursor, 2).unwrap();
+  timestamps.extend(page.items);
+  cursor = match page.next_cursor {
+   Some(next_cursor) => Some(next_cursor),
+   None => break,
+  };
+ }
+ assert!(timestamps == [None, Some(1), Some(2), Some(3), Some(4), Some(5)]);
+
+ // a page of 0 rows continues from where it started
+
+ let page =
+  select!(Page<PageIntegrationTest> "ORDER BY timestamp, rowid", None::<&str>, 0).unwrap();
+ assert!(page.items.is_empty());
+ let page =
+  select!(Page<PageIntegrationTest> "ORDER BY timestamp, rowid", page.next_cursor, 2).unwrap();
+ assert!(page.items.iter().map(|r| r.name.clone().unwrap()).collect::<Vec<_>>() == ["g", "h"]);
+ let page =
+  select!(Page<PageIntegrationTest> "ORDER BY timestamp, rowid", page.next_cursor, 0).unwrap();
+ let page =
+  select!(Page<PageIntegrationTest> "ORDER BY timestamp, rowid", page.next_cursor, 2).unwrap();
+ assert!(page.items.iter().map(|r| r.name.clone().unwrap()).collect::<Vec<_>>() == ["a", "b"]);
+}
+
+#[derive(Turbosql, Default, Debug, PartialEq, Clone)]
+#[turbosql(soft_delete)]
+struct SoftDeleteIntegrationTest {
+ rowid: Option<i64>,
+ name: Option<String>,
+}
+
+#[test]
+fn soft_delete() {
+ let a = SoftDeleteIntegrationTest { rowid: None, name: Some("a".into()) };
+ let a = SoftDeleteIntegrationTest { rowid: Some(a.insert().unwrap()), ..a };
+ let b = SoftDeleteIntegrationTest { rowid: None, name: Some("b".into()) };
+ let b = SoftDeleteIntegrationTest { rowid: Some(b.insert().unwrap()), ..b };
+
+ assert!(a.delete().unwrap() == 1);
+ assert!(a.delete().unwrap() == 0);
+
+ // derived selects and select!'s generated SELECT ... FROM skip deleted rows
+
+ assert!(SoftDeleteIntegrationTest::select_all().unwrap() == vec![b.clone()]);
+ assert!(SoftDeleteIntegrationTest::select_one_where("rowid = ?", [a.rowid]).is_err());
+ assert!(SoftDeleteIntegrationTest::query().count().unwrap() == 1);
+ assert!(select!(Vec<SoftDeleteIntegrationTest>).unwrap() == vec![b.clone()]);
+ assert!(select!(Option<SoftDeleteIntegrationTest> "WHERE name = ?", "a").unwrap().is_none());
+ assert!(
+  select!(Page<SoftDeleteIntegrationTest> "ORDER BY rowid", None::<&str>, 10).unwrap().items
+   == vec![b.clone()]
+ );
+
+ // unless asked not to, or the SQL names the table itself
+
+ assert!(
+  SoftDeleteIntegrationTest::select_all_including_deleted().unwrap() == vec![a.clone(), b.clone()]
+ );
+ assert!(
+  SoftDeleteIntegrationTest::select_where_including_deleted("name = ?", ["a"]).unwrap()
+   == vec![a.clone()]
+ );
+ assert!(SoftDeleteIntegrationTest::query_including_deleted().count().unwrap() == 2);
+ let deleted_at =
+  select!(i64 "deleted_at FROM softdeleteintegrationtest WHERE rowid = ?", a.rowid).unwrap();
+ assert!(deleted_at > 1_600_000_000_000); // ms since the Unix epoch
+
+ assert!(a.restore().unwrap() == 1);
+ assert!(a.restore().unwrap() == 0);
+ assert!(SoftDeleteIntegrationTest::select_all().unwrap() == vec![a, b]);
+}
+
+#[derive(Turbosql, Default, Debug, PartialEq, Clone)]
+#[turbosql(soft_delete)]
+struct TimestampIntegrationTest {
+ rowid: Option<i64>,
+ name: Option<String>,
+ #[turbosql(created_at)]
+ created_at: Option<i64>,
+ #[turbosql(updated_at)]
+ updated_at: f64,
+}
+
+#[test]
+fn timestamps() {
+ let wait = || std::thread::sleep(std::time::Duration::from_millis(5));
+
+ let mut row = TimestampIntegrationTest {
+  rowid: None,
+  name: Some("a".into()),
+  created_at: Some(1),
+  updated_at: 1.0,
+ };
+ let rowid = row.insert_mut().unwrap();
+ let created_at = row.created_at.unwrap();
+ assert!(created_at > 1_600_000_000_000 && row.updated_at == created_at as f64);
+ assert!(select!(TimestampIntegrationTest "WHERE rowid = ?", rowid).unwrap() == row);
+
+ // update sets updated_at, and never changes created_at
+
+ wait();
+ TimestampIntegrationTest { created_at: None, name: Some("b".into()), ..row.clone() }
+  .update()
+  .unwrap();
+ let row = select!(TimestampIntegrationTest "WHERE rowid = ?", rowid).unwrap();
+ assert!(row.created_at == Some(created_at) && row.updated_at > created_at as f64);
+
+ wait();
+ TimestampIntegrationTest { created_at: None, ..row.clone() }.upsert().unwrap();
+ let upserted = select!(TimestampIntegrationTest "WHERE rowid = ?", rowid).unwrap();
+ assert!(upserted.created_at == Some(created_at) && upserted.updated_at > row.updated_at);
+
+ // so do soft deletes
+
+ wait();
+ row.delete().unwrap();
+ let updated_at =
+  select!(f64 "updated_at FROM timestampintegrationtest WHERE rowid = ?", rowid).unwrap();
+ assert!(updated_at > upserted.updated_at);
+
+ let rows = vec![TimestampIntegrationTest { rowid: None, created_at: None, ..row }; 2];
+ TimestampIntegrationTest::insert_batch(&rows).unwrap();
+ assert!(
+  select!(i64 "COUNT(*) FROM timestampintegrationtest WHERE created_at > ?", created_at).unwrap()
+   == 2
+ );
 }
 
 #[test]
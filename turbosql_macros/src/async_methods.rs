@@ -0,0 +1,50 @@
+use super::Table;
+use quote::{quote, quote_spanned};
+
+/// insert_async, update_async etc., which take the row by value and run on Turbosql's thread pool
+pub(super) fn async_methods(table: &Table) -> proc_macro2::TokenStream {
+ let restore_async = match table.soft_delete {
+  true => quote_spanned! { table.span =>
+   /// Like [`restore`](Self::restore), on Turbosql's thread pool.
+   #[allow(dead_code)]
+   pub fn restore_async(self) -> ::turbosql::Task<::turbosql::Result<usize>> {
+    ::turbosql::spawn_blocking(move || self.restore())
+   }
+  },
+  false => quote!(),
+ };
+
+ quote_spanned! { table.span =>
+  /// Like [`insert`](Self::insert), on Turbosql's thread pool.
+  #[allow(dead_code)]
+  pub fn insert_async(self) -> ::turbosql::Task<::turbosql::Result<i64>> {
+   ::turbosql::spawn_blocking(move || self.insert())
+  }
+
+  /// Like [`insert_batch`](Self::insert_batch), on Turbosql's thread pool.
+  #[allow(dead_code)]
+  pub fn insert_batch_async(rows: Vec<#table>) -> ::turbosql::Task<::turbosql::Result<Vec<i64>>> {
+   ::turbosql::spawn_blocking(move || #table::insert_batch(&rows))
+  }
+
+  /// Like [`update`](Self::update), on Turbosql's thread pool.
+  #[allow(dead_code)]
+  pub fn update_async(self) -> ::turbosql::Task<::turbosql::Result<usize>> {
+   ::turbosql::spawn_blocking(move || self.update())
+  }
+
+  /// Like [`upsert`](Self::upsert), on Turbosql's thread pool.
+  #[allow(dead_code)]
+  pub fn upsert_async(self) -> ::turbosql::Task<::turbosql::Result<i64>> {
+   ::turbosql::spawn_blocking(move || self.upsert())
+  }
+
+  /// Like [`delete`](Self::delete), on Turbosql's thread pool.
+  #[allow(dead_code)]
+  pub fn delete_async(self) -> ::turbosql::Task<::turbosql::Result<usize>> {
+   ::turbosql::spawn_blocking(move || self.delete())
+  }
+
+  #restore_async
+ }
+}
@@ -0,0 +1,27 @@
+use super::Table;
+use quote::{format_ident, quote_spanned};
+
+/// {member}_blob() for each BLOB member, opening it for incremental I/O
+pub(super) fn blob(table: &Table) -> proc_macro2::TokenStream {
+ let database = table.database();
+ let table_name = &table.name;
+
+ let accessors =
+  table.columns.iter().filter(|c| c.sql_type.starts_with("BLOB") && !c.json).map(|c| {
+   let fn_name = format_ident!("{}_blob", c.ident);
+   let column_name = &c.name;
+
+   quote_spanned! { c.span =>
+    /// Opens this member's `BLOB` for reading and writing in place, without loading it.
+    #[allow(dead_code)]
+    pub fn #fn_name(&self) -> ::turbosql::Result<::turbosql::BlobHandle> {
+     assert!(self.rowid.is_some());
+     ::turbosql::BlobHandle::open_in(#database, #table_name, #column_name, self.rowid.unwrap())
+    }
+   }
+  });
+
+ quote_spanned! { table.span =>
+  #(#accessors)*
+ }
+}
@@ -1,8 +1,9 @@
-use super::{MigrationsToml, Table};
+use super::{sql, MigrationsToml, Table};
 use proc_macro_error::abort_call_site;
 use quote::quote;
-use rusqlite::params;
+use rusqlite::{params, Connection};
 use serde::Serialize;
+use std::collections::HashSet;
 use std::fs;
 
 #[cfg(not(feature = "test"))]
@@ -14,14 +15,12 @@ const MIGRATIONS_FILENAME: &str = "test.migrations.toml";
 pub(super) fn create(table: &Table) -> proc_macro2::TokenStream {
  // create the migrations
 
- let sql = makesql_create(&table);
+ let sql = makesql_create(table);
 
  rusqlite::Connection::open_in_memory().unwrap().execute(&sql, params![]).unwrap_or_else(|e| {
   abort_call_site!("Error validating auto-generated CREATE TABLE statement: {} {:#?}", sql, e)
  });
 
- let target_migrations = make_migrations(&table);
-
  // read in the existing migrations from toml
 
  let lockfile = std::fs::File::create(std::env::temp_dir().join("migrations.toml.lock")).unwrap();
@@ -41,23 +40,12 @@ pub(super) fn create(table: &Table) -> proc_macro2::TokenStream {
   abort_call_site!("Unable to decode toml in {}: {:?}", migrations_toml_path_lossy, e)
  });
 
- // add any migrations that aren't already present
+ // add the migrations that bring the table from its current state to the struct
 
  let mut output_migrations: Vec<String> =
-  source_migrations_toml.migrations_append_only.clone().unwrap_or_default();
-
- target_migrations.iter().for_each(|m1| {
-  if source_migrations_toml
-   .migrations_append_only
-   .clone()
-   .unwrap_or_default()
-   .iter()
-   .find(|m2| m2 == &m1)
-   .is_none()
-  {
-   output_migrations.push(m1.clone());
-  }
- });
+  source_migrations_toml.migrations_append_only.unwrap_or_default();
+ let mut new_migrations = make_migrations(table, &output_migrations);
+ output_migrations.append(&mut new_migrations);
 
  // save to toml
 
@@ -96,23 +84,204 @@ fn makesql_create(table: &Table) -> String {
  format!(
   "CREATE TABLE {} ({})",
   table.name,
-  table.columns.iter().map(|c| format!("{} {}", c.name, c.sql_type)).collect::<Vec<_>>().join(",")
+  table
+   .columns
+   .iter()
+   .map(|c| format!("{} {}", c.name, c.sql_type))
+   .chain(Some("deleted_at INTEGER".to_string()).filter(|_| table.soft_delete))
+   .collect::<Vec<_>>()
+   .join(",")
  )
 }
 
-fn make_migrations(table: &Table) -> Vec<String> {
- let mut vec = vec![format!("CREATE TABLE {} (rowid INTEGER PRIMARY KEY)", table.name)];
+/// The table as `migrations` leave it.
+struct TableState {
+ /// Each column's name and the rest of its definition, or `None` if there's no such table.
+ columns: Option<Vec<(String, String)>>,
+ /// Every column the table has had, lowercased.
+ ever_columns: HashSet<String>,
+ indexes: Vec<String>,
+}
+
+impl TableState {
+ fn replay(table_name: &str, migrations: &[String]) -> TableState {
+  let mut ever_columns = HashSet::new();
+
+  let tempdb = super::migrations_to_tempdb_with(migrations, |db| {
+   let columns = query_strings(db, "SELECT name FROM pragma_table_info(?)", table_name);
+   ever_columns.extend(columns.iter().map(|name| name.to_lowercase()));
+  });
+
+  let create_sql = query_strings(
+   &tempdb,
+   "SELECT sql FROM sqlite_master WHERE type = 'table' AND name = ?",
+   table_name,
+  );
+
+  TableState {
+   columns: create_sql.first().map(|sql| sql::column_defs(sql)),
+   ever_columns,
+   indexes: query_strings(
+    &tempdb,
+    "SELECT name FROM sqlite_master WHERE type = 'index' AND tbl_name = ?",
+    table_name,
+   ),
+  }
+ }
+
+ fn column(&self, name: &str) -> Option<&(String, String)> {
+  self.columns.iter().flatten().find(|(n, _)| n.eq_ignore_ascii_case(name))
+ }
+}
+
+fn query_strings(db: &Connection, sql: &str, param: &str) -> Vec<String> {
+ db
+  .prepare(sql)
+  .and_then(|mut stmt| {
+   stmt.query_map(params![param], |row| row.get(0))?.collect::<Result<Vec<String>, _>>()
+  })
+  .unwrap_or_else(|e| abort_call_site!("Reading {:?} for {}: {:?}", sql, param, e))
+}
+
+/// The migrations that bring the table, as `migrations` leave it, in line with the struct:
+/// new columns are added and renamed ones renamed. A changed column definition or a
+/// #[turbosql(drop_column)] column that still exists needs a rebuild instead, since SQLite 3.33
+/// can't alter or drop columns.
+fn make_migrations(table: &Table, migrations: &[String]) -> Vec<String> {
+ let state = TableState::replay(&table.name, migrations);
+
+ let columns = table
+  .columns
+  .iter()
+  .map(|c| (c.name.as_str(), c.sql_type.as_str(), c.renamed_from.as_slice()))
+  .chain(Some(("deleted_at", "INTEGER", &[][..])).filter(|_| table.soft_delete))
+  .collect::<Vec<_>>();
+
+ for name in &table.drop_columns {
+  if columns.iter().any(|(c, _, _)| c.eq_ignore_ascii_case(name)) {
+   abort_call_site!("drop_column {:?} is also a member of {}", name, table.name);
+  }
+  // a dropped column that was never a column is probably a typo
+  if !state.ever_columns.contains(&name.to_lowercase()) {
+   abort_call_site!(
+    "drop_column {:?} was never a column of {}, so there's nothing to drop",
+    name,
+    table.name
+   );
+  }
+ }
+
+ let mut vec = Vec::new();
+ let mut alters = Vec::new();
+
+ if state.columns.is_none() {
+  vec.push(format!("CREATE TABLE {} (rowid INTEGER PRIMARY KEY)", table.name));
+ }
+
+ let mut rebuild = table.drop_columns.iter().any(|name| state.column(name).is_some());
+
+ // each column's current name, if it has one; renamed_from names are tried most recent first
+
+ let sources = columns
+  .iter()
+  .map(|(name, sql_type, renamed_from)| {
+   let existing =
+    state.column(name).or_else(|| renamed_from.iter().rev().find_map(|old| state.column(old)));
+
+   match existing {
+    Some((source, def)) => {
+     rebuild |= sql::normalize(def) != sql::normalize(sql_type);
+     if !source.eq_ignore_ascii_case(name) {
+      alters.push(format!("ALTER TABLE {} RENAME COLUMN {} TO {}", table.name, source, name));
+     }
+     Some(source.as_str())
+    }
+    None => {
+     if !matches!((*name, *sql_type), ("rowid", "INTEGER PRIMARY KEY")) {
+      alters.push(format!("ALTER TABLE {} ADD COLUMN {} {}", table.name, name, sql_type));
+     }
+     None
+    }
+   }
+  })
+  .collect::<Vec<_>>();
+
+ if !rebuild {
+  vec.append(&mut alters);
+  vec.extend(
+   makesql_indexes(table)
+    .into_iter()
+    .filter(|(name, _)| !state.indexes.contains(name))
+    .map(|(_, sql)| sql),
+  );
+  return vec;
+ }
+
+ // Rebuild the table in one go, instead of the ALTERs above. Columns that aren't members or
+ // dropped are kept as they are.
 
- let mut alters = table
+ let kept = state
   .columns
   .iter()
-  .filter_map(|c| match (c.name.as_str(), c.sql_type) {
-   ("rowid", "INTEGER PRIMARY KEY") => None,
-   _ => Some(format!("ALTER TABLE {} ADD COLUMN {} {}", table.name, c.name, c.sql_type)),
+  .flatten()
+  .filter(|(name, _)| {
+   !sources.iter().flatten().any(|source| source.eq_ignore_ascii_case(name))
+    && !table.drop_columns.iter().any(|d| d.eq_ignore_ascii_case(name))
   })
   .collect::<Vec<_>>();
 
- vec.append(&mut alters);
+ let rebuild_name = format!("turbosql_rebuild_{}", table.name);
+
+ let defs = columns
+  .iter()
+  .map(|(name, sql_type, _)| format!("{} {}", name, sql_type))
+  .chain(kept.iter().map(|(name, def)| format!("{} {}", name, def)))
+  .collect::<Vec<_>>();
+
+ let (targets, sources): (Vec<_>, Vec<_>) = columns
+  .iter()
+  .zip(&sources)
+  .filter_map(|((name, _, _), source)| Some((*name, (*source)?)))
+  .chain(kept.iter().map(|(name, _)| (name.as_str(), name.as_str())))
+  .unzip();
+
+ vec.push(format!("CREATE TABLE {} ({})", rebuild_name, defs.join(",")));
+ vec.push(format!(
+  "INSERT INTO {} ({}) SELECT {} FROM {}",
+  rebuild_name,
+  targets.join(", "),
+  sources.join(", "),
+  table.name
+ ));
+ vec.push(format!("DROP TABLE {}", table.name));
+ vec.push(format!("ALTER TABLE {} RENAME TO {}", rebuild_name, table.name));
+
+ // dropping the table dropped its indexes, too
+ vec.extend(makesql_indexes(table).into_iter().map(|(_, sql)| sql));
 
  vec
 }
+
+/// Each index's name and `CREATE INDEX` statement.
+fn makesql_indexes(table: &Table) -> Vec<(String, String)> {
+ table
+  .indexes
+  .iter()
+  .map(|index| {
+   let name = format!(
+    "{}_{}_{}",
+    table.name,
+    index.columns.join("_"),
+    if index.unique { "unique" } else { "index" }
+   );
+   let sql = format!(
+    "CREATE {}INDEX {} ON {} ({})",
+    if index.unique { "UNIQUE " } else { "" },
+    name,
+    table.name,
+    index.columns.join(", ")
+   );
+   (name, sql)
+  })
+  .collect()
+}
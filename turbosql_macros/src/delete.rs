@@ -0,0 +1,80 @@
+use super::{Table, Timestamp};
+use quote::quote_spanned;
+
+/// DELETE FROM tablename WHERE rowid = ?
+/// or for #[turbosql(soft_delete)], UPDATE tablename SET deleted_at = ?1 WHERE rowid = ?2, and restore()
+pub(super) fn delete(table: &Table) -> proc_macro2::TokenStream {
+ if table.soft_delete {
+  return soft_delete(table);
+ }
+
+ let sql = makesql_delete(table);
+
+ super::validate_sql_or_abort(&sql);
+
+ let database = table.database();
+
+ quote_spanned! { table.span =>
+  /// Deletes the row with this `rowid`. Returns 0 if there's no such row.
+  #[allow(dead_code)]
+  pub fn delete(&self) -> ::turbosql::Result<usize> {
+   ::turbosql::__require_rowid(self.rowid, "delete")?;
+   #database.__with_db(|db| {
+    let mut stmt = db.prepare_cached(#sql)?;
+    stmt.execute(::turbosql::params![self.rowid])
+   })
+  }
+ }
+}
+
+fn soft_delete(table: &Table) -> proc_macro2::TokenStream {
+ // deleting or restoring a row also counts as updating it
+
+ let updated_at = table
+  .columns
+  .iter()
+  .filter(|c| c.timestamp == Some(Timestamp::UpdatedAt))
+  .map(|c| format!(", {} = ?1", c.name))
+  .collect::<String>();
+
+ let sql_delete = format!(
+  "UPDATE {} SET deleted_at = ?1{} WHERE rowid = ?2 AND deleted_at IS NULL",
+  table.name, updated_at
+ );
+ let sql_restore = format!(
+  "UPDATE {} SET deleted_at = NULL{} WHERE rowid = ?2 AND deleted_at IS NOT NULL",
+  table.name, updated_at
+ );
+
+ super::validate_sql_or_abort(&sql_delete);
+ super::validate_sql_or_abort(&sql_restore);
+
+ let database = table.database();
+
+ quote_spanned! { table.span =>
+  /// Marks this row deleted, so that derived selects skip it, by setting its `deleted_at`.
+  /// Returns 0 if it was already deleted.
+  #[allow(dead_code)]
+  pub fn delete(&self) -> ::turbosql::Result<usize> {
+   ::turbosql::__require_rowid(self.rowid, "delete")?;
+   #database.__with_db(|db| {
+    let mut stmt = db.prepare_cached(#sql_delete)?;
+    stmt.execute(::turbosql::params![::turbosql::__now_millis(), self.rowid])
+   })
+  }
+
+  /// Undoes [`delete`](Self::delete). Returns 0 if this row wasn't deleted.
+  #[allow(dead_code)]
+  pub fn restore(&self) -> ::turbosql::Result<usize> {
+   ::turbosql::__require_rowid(self.rowid, "restore")?;
+   #database.__with_db(|db| {
+    let mut stmt = db.prepare_cached(#sql_restore)?;
+    stmt.execute(::turbosql::params![::turbosql::__now_millis(), self.rowid])
+   })
+  }
+ }
+}
+
+fn makesql_delete(table: &Table) -> String {
+ format!("DELETE FROM {} WHERE rowid = ?", table.name)
+}
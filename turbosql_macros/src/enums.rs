@@ -0,0 +1,147 @@
+use super::{MiniEnum, ENUMS};
+use proc_macro_error::abort;
+use quote::{format_ident, quote_spanned};
+use syn::spanned::Spanned;
+use syn::{DataEnum, DeriveInput, Expr, Fields, Lit, Meta, NestedMeta};
+
+/// ToSql, FromSql and TurbosqlType for a fieldless enum, stored as its variant names (TEXT)
+/// or, with #[turbosql(repr = "int")], its discriminants (INTEGER)
+pub(super) fn enum_derive(input: &DeriveInput, data: &DataEnum) -> proc_macro2::TokenStream {
+ let ident = &input.ident;
+ let span = input.span();
+ let repr_int = repr_int(input);
+
+ let mut discriminant = -1i64;
+ let variants = data
+  .variants
+  .iter()
+  .map(|v| {
+   if !matches!(v.fields, Fields::Unit) {
+    abort!(v, "derive(Turbosql) enums can only have fieldless variants");
+   }
+   discriminant = match &v.discriminant {
+    Some((_, Expr::Lit(syn::ExprLit { lit: Lit::Int(lit), .. }))) => {
+     lit.base10_parse().unwrap_or_else(|e| abort!(lit, "{}", e))
+    }
+    Some((_, expr)) => abort!(expr, "derive(Turbosql) enum discriminants must be integer literals"),
+    None => discriminant + 1,
+   };
+   (&v.ident, v.ident.to_string(), discriminant)
+  })
+  .collect::<Vec<_>>();
+
+ if variants.is_empty() {
+  abort!(span, "derive(Turbosql) enums must have at least one variant");
+ }
+
+ let (affinity, sql_type, values) = match repr_int {
+  true => (
+   quote_spanned!(span=> Integer),
+   "INTEGER",
+   variants.iter().map(|(_, _, d)| d.to_string()).collect(),
+  ),
+  false => (
+   quote_spanned!(span=> Text),
+   "TEXT",
+   variants.iter().map(|(_, name, _)| format!("'{}'", name)).collect(),
+  ),
+ };
+
+ let marker = marker(sql_type, &variants);
+
+ ENUMS.lock().unwrap().insert(
+  ident.to_string(),
+  MiniEnum { sql_type: sql_type.to_string(), values, marker: marker.to_string() },
+ );
+
+ let to_sql = variants.iter().map(|(v, name, d)| match repr_int {
+  true => quote_spanned!(span=> #ident::#v => ::turbosql::ToSqlOutput::from(#d)),
+  false => quote_spanned!(span=> #ident::#v => ::turbosql::ToSqlOutput::from(#name)),
+ });
+
+ let from_sql = match repr_int {
+  true => {
+   let arms = variants.iter().map(|(v, _, d)| quote_spanned!(span=> #d => Ok(#ident::#v)));
+   quote_spanned! { span =>
+    match value.as_i64()? {
+     #(#arms,)*
+     other => Err(::turbosql::FromSqlError::Other(
+      format!("{} is not a valid {}", other, stringify!(#ident)).into(),
+     )),
+    }
+   }
+  }
+  false => {
+   let arms = variants.iter().map(|(v, name, _)| quote_spanned!(span=> #name => Ok(#ident::#v)));
+   quote_spanned! { span =>
+    match value.as_str()? {
+     #(#arms,)*
+     other => Err(::turbosql::FromSqlError::Other(
+      format!("{:?} is not a valid {}", other, stringify!(#ident)).into(),
+     )),
+    }
+   }
+  }
+ };
+
+ quote_spanned! { span =>
+  impl ::turbosql::ToSql for #ident {
+   fn to_sql(&self) -> ::turbosql::Result<::turbosql::ToSqlOutput<'_>> {
+    Ok(match self {
+     #(#to_sql,)*
+    })
+   }
+  }
+
+  impl ::turbosql::FromSql for #ident {
+   fn column_result(value: ::turbosql::ValueRef<'_>) -> ::turbosql::FromSqlResult<Self> {
+    #from_sql
+   }
+  }
+
+  impl ::turbosql::TurbosqlType for #ident {
+   type Affinity = ::turbosql::affinity::#affinity;
+  }
+
+  impl #ident {
+   #[doc(hidden)]
+   #[allow(non_upper_case_globals)]
+   pub const #marker: () = ();
+  }
+ }
+}
+
+/// ENUMS is keyed by ident, since that's all a struct member's type tells the derive. Struct
+/// members refer to this const, e.g. `__turbosql_TEXT_Name_Size`, so that if the member's type is
+/// a different enum, or a different version of this one, it doesn't compile.
+fn marker(sql_type: &str, variants: &[(&syn::Ident, String, i64)]) -> syn::Ident {
+ let values = variants.iter().map(|(_, name, d)| match sql_type {
+  "INTEGER" => d.to_string().replace('-', "minus"),
+  _ => name.clone(),
+ });
+ format_ident!("__turbosql_{}_{}", sql_type, values.collect::<Vec<_>>().join("_"))
+}
+
+fn repr_int(input: &DeriveInput) -> bool {
+ let mut repr_int = false;
+
+ for attr in &input.attrs {
+  match attr.parse_meta() {
+   Ok(Meta::List(list)) if list.path.is_ident("turbosql") => {
+    for value in list.nested.iter() {
+     match value {
+      NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("repr") => match &nv.lit {
+       Lit::Str(lit) if lit.value() == "int" => repr_int = true,
+       Lit::Str(lit) if lit.value() == "text" => repr_int = false,
+       lit => abort!(lit, "Expected repr = \"int\" or repr = \"text\""),
+      },
+      _ => abort!(value, "Unknown turbosql attribute for enums"),
+     }
+    }
+   }
+   _ => (),
+  }
+ }
+
+ repr_int
+}
@@ -1,37 +1,74 @@
 use super::Table;
-use quote::quote_spanned;
+use quote::{format_ident, quote_spanned};
 
 /// INSERT INTO tablename (name1, name2...) VALUES (?1, ?2...)
 pub(super) fn insert(table: &Table) -> proc_macro2::TokenStream {
- let sql = makesql_insert(&table);
+ let sql = makesql_insert(table);
 
  super::validate_sql_or_abort(&sql);
 
+ let database = table.database();
+
  // let idents = table.columns.iter().map(|c| &c.ident).collect::<Vec<_>>();
- let columns = table
-  .columns
-  .iter()
-  .map(|c| {
-   let ident = &c.ident;
-   quote_spanned!(c.span=> &self.#ident as &dyn ::turbosql::ToSql)
+ let columns =
+  |row: &proc_macro2::Ident| table.columns.iter().map(|c| c.bind(row)).collect::<Vec<_>>();
+
+ let self_columns = columns(&format_ident!("self"));
+ let row_columns = columns(&format_ident!("row"));
+
+ // created_at and updated_at members are set to the time of the insert
+
+ let now = table.now();
+ let set_now = table.columns.iter().filter_map(|c| c.set_now(&format_ident!("self")));
+
+ let insert_self = quote_spanned! { table.span =>
+  #database.__with_db(|db| -> ::turbosql::Result<i64> {
+   let mut stmt = db.prepare_cached(#sql)?;
+   stmt.execute(&[#(#self_columns),*] as &[&dyn ::turbosql::ToSql])?;
+   Ok(db.last_insert_rowid())
   })
-  .collect::<Vec<_>>();
+ };
 
  quote_spanned! { table.span =>
+  /// Inserts this row and returns the `rowid` SQLite assigned to it.
   #[allow(dead_code)]
-  pub fn insert(&self) -> ::turbosql::Result<usize> {
+  pub fn insert(&self) -> ::turbosql::Result<i64> {
    // #table::__turbosql_ensure_table_created();
    assert!(self.rowid.is_none());
-   let db = ::turbosql::__TURBOSQL_DB.lock().unwrap();  // todo: use tokio's lock?
-   let mut stmt = db.prepare_cached(#sql)?;
-   stmt.execute(&[#(#columns),*] as &[&dyn ::turbosql::ToSql])
+   #now
+   #insert_self
+  }
+
+  /// Inserts this row and sets `self.rowid` to the newly assigned `rowid`, and any
+  /// `created_at`/`updated_at` members to the time of the insert.
+  #[allow(dead_code)]
+  pub fn insert_mut(&mut self) -> ::turbosql::Result<i64> {
+   assert!(self.rowid.is_none());
+   #now
+   let rowid = #insert_self?;
+   self.rowid = Some(rowid);
+   #(#set_now)*
+   Ok(rowid)
   }
 
+  /// Inserts all rows in a single transaction, returning their new `rowid`s in order.
+  /// If any insert fails, none of the rows are inserted.
   #[allow(dead_code)]
-  pub fn insert_batch(rows: &[#table]) {
-   for row in rows {
-    row.insert().unwrap();
-   }
+  pub fn insert_batch(rows: &[#table]) -> ::turbosql::Result<Vec<i64>> {
+   #now
+   #database.transaction(|| {
+    #database.__with_db(|db| {
+     let mut stmt = db.prepare_cached(#sql)?;
+     rows
+      .iter()
+      .map(|row| {
+       assert!(row.rowid.is_none());
+       stmt.execute(&[#(#row_columns),*] as &[&dyn ::turbosql::ToSql])?;
+       Ok(db.last_insert_rowid())
+      })
+      .collect()
+    })
+   })
   }
  }
 }
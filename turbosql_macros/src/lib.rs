@@ -8,17 +8,18 @@ const SQLITE_64BIT_ERROR: &str = r##"Sadly, SQLite cannot natively store unsigne
 use once_cell::sync::Lazy;
 use proc_macro2::Span;
 use proc_macro_error::{abort, abort_call_site, proc_macro_error};
-use quote::{format_ident, quote, ToTokens};
-use rusqlite::{params, Connection, Statement};
+use quote::{format_ident, quote, quote_spanned, ToTokens};
+use rusqlite::{params, Connection};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::Mutex;
+use syn::parse::discouraged::Speculative;
 use syn::parse::{Parse, ParseStream};
 use syn::punctuated::Punctuated;
 use syn::spanned::Spanned;
 use syn::{
  parse_macro_input, Data, DeriveInput, Expr, Fields, FieldsNamed, Ident, LitStr, Meta, NestedMeta,
- Token, Type,
+ Token, Type, Visibility,
 };
 
 #[cfg(not(feature = "test"))]
@@ -26,9 +27,18 @@ const MIGRATIONS_FILENAME: &str = "migrations.toml";
 #[cfg(feature = "test")]
 const MIGRATIONS_FILENAME: &str = "test.migrations.toml";
 
+mod async_methods;
+mod blob;
 mod create;
+mod delete;
+mod enums;
 mod insert;
+mod query;
+mod references;
 mod select;
+mod sql;
+mod update;
+mod upsert;
 
 // trait Ok<T> {
 //  fn ok(self) -> Result<T, anyhow::Error>;
@@ -43,15 +53,35 @@ mod select;
 #[derive(Debug, Clone)]
 struct Table {
  ident: Ident,
+ vis: Visibility,
  span: Span,
  name: String,
  columns: Vec<Column>,
+ indexes: Vec<Index>,
+ drop_columns: Vec<String>,
+ database: Option<String>,
+ soft_delete: bool,
+}
+
+/// Struct-level #[turbosql(...)] attributes
+struct TableAttrs {
+ indexes: Vec<Index>,
+ drop_columns: Vec<String>,
+ database: Option<String>,
+ soft_delete: bool,
+}
+
+#[derive(Debug, Clone)]
+struct Index {
+ columns: Vec<String>,
+ unique: bool,
 }
 
 #[derive(Debug)]
 struct MiniTable {
- name: String,
  columns: Vec<MiniColumn>,
+ database: Option<String>,
+ soft_delete: bool,
 }
 
 impl ToTokens for Table {
@@ -61,20 +91,120 @@ impl ToTokens for Table {
  }
 }
 
+impl Table {
+ /// `&Database` expression for this table's #[turbosql(database = "...")], or the default.
+ fn database(&self) -> proc_macro2::TokenStream {
+  database_tokens(self.database.as_deref())
+ }
+
+ /// `let __turbosql_now = ...;` for generated fns that bind timestamp members, if there are any.
+ fn now(&self) -> proc_macro2::TokenStream {
+  match self.columns.iter().any(|c| c.timestamp.is_some()) {
+   true => quote!(let __turbosql_now = ::turbosql::__now_millis();),
+   false => quote!(),
+  }
+ }
+
+ /// What generated SELECTs read `FROM`; see `from_sql`.
+ fn from(&self) -> String {
+  from_sql(&self.name, self.soft_delete)
+ }
+}
+
+/// The table, or for #[turbosql(soft_delete)], its rows that aren't deleted, under the table's
+/// name, so that a WHERE clause after it reads the same either way.
+fn from_sql(table_name: &str, soft_delete: bool) -> String {
+ match soft_delete {
+  true => format!("(SELECT * FROM {0} WHERE deleted_at IS NULL) AS {0}", table_name),
+  false => table_name.to_string(),
+ }
+}
+
+fn database_tokens(database: Option<&str>) -> proc_macro2::TokenStream {
+ match database {
+  Some(name) => quote!(::turbosql::named_database(#name)),
+  None => quote!(::turbosql::database()),
+ }
+}
+
 #[derive(Debug, Clone)]
 struct Column {
  ident: Ident,
+ vis: Visibility,
  span: Span,
  name: String,
- rust_type: String,
- sql_type: &'static str,
+ sql_type: String,
+ ty: Type,
+ affinity: Ident,
+ json: bool,
+ /// For derive(Turbosql) enum members, the enum's `MiniEnum::marker`.
+ enum_marker: Option<String>,
+ index: bool,
+ unique: bool,
+ references: Option<Reference>,
+ renamed_from: Vec<String>,
+ timestamp: Option<Timestamp>,
+}
+
+/// #[turbosql(created_at)] or #[turbosql(updated_at)]: set by generated code, in ms since the
+/// Unix epoch
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Timestamp {
+ CreatedAt,
+ UpdatedAt,
+}
+
+/// #[turbosql(references = "Struct")] or #[turbosql(references = "Struct.column")]
+#[derive(Debug, Clone)]
+struct Reference {
+ span: Span,
+ table_ident: Ident,
+ table_name: String,
+ column: Ident,
+ on_delete: Option<String>,
+}
+
+impl Column {
+ /// `&dyn ToSql` expression binding this member of `row`, or for timestamp members, the
+ /// generated fn's `__turbosql_now`; see `Table::now`.
+ fn bind(&self, row: &Ident) -> proc_macro2::TokenStream {
+  let ident = &self.ident;
+  match (self.json, self.timestamp) {
+   (_, Some(_)) => quote_spanned!(self.span=> &__turbosql_now as &dyn ::turbosql::ToSql),
+   // JSON null is stored as the text `null`, but `None` as SQL `NULL`
+   (true, _) => match option_inner_type(&self.ty) {
+    Some(_) => quote_spanned! {self.span=>
+     &#row.#ident.as_ref().map(::turbosql::Json) as &dyn ::turbosql::ToSql
+    },
+    None => quote_spanned!(self.span=> &::turbosql::Json(&#row.#ident) as &dyn ::turbosql::ToSql),
+   },
+   (false, _) => quote_spanned!(self.span=> &#row.#ident as &dyn ::turbosql::ToSql),
+  }
+ }
+
+ /// For timestamp members, sets this member of `row` to `__turbosql_now`.
+ fn set_now(&self, row: &Ident) -> Option<proc_macro2::TokenStream> {
+  self.timestamp?;
+  let ident = &self.ident;
+  let inner_ty = option_inner_type(&self.ty).unwrap_or(&self.ty);
+  let now = match quote!(#inner_ty).to_string().as_str() {
+   "f64" => quote!(__turbosql_now as f64),
+   _ => quote!(__turbosql_now),
+  };
+  let now = match option_inner_type(&self.ty) {
+   Some(_) => quote!(Some(#now)),
+   None => now,
+  };
+  Some(quote_spanned!(self.span=> #row.#ident = #now;))
+ }
 }
 
 #[derive(Debug)]
 struct MiniColumn {
  name: String,
- rust_type: String,
- sql_type: &'static str,
+ json: bool,
+ /// Has a unique index of its own, so it can be referenced.
+ unique: bool,
 }
 
 // static TEST_DB: Lazy<Mutex<Connection>> =
@@ -84,6 +214,21 @@ static LAST_TABLE_NAME: Lazy<Mutex<String>> = Lazy::new(|| Mutex::new("none".to_
 
 static TABLES: Lazy<Mutex<HashMap<String, MiniTable>>> = Lazy::new(|| Mutex::new(HashMap::new()));
 
+#[derive(Debug)]
+struct MiniEnum {
+ sql_type: String,
+ /// SQL literals for each variant, for the CHECK constraint; the first is the default of
+ /// non-Option members
+ values: Vec<String>,
+ /// Hidden const on the enum naming its variants, which struct members of the enum's type
+ /// refer to, so that an ident shared with another type fails to compile; see `enums::marker`.
+ marker: String,
+}
+
+/// derive(Turbosql) enums, keyed by ident, so that struct members of these types get their SQL type,
+/// default and CHECK constraint
+static ENUMS: Lazy<Mutex<HashMap<String, MiniEnum>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
 // #[proc_macro]
 // pub fn set_db_path(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
 //  let input = proc_macro2::TokenStream::from(input);
@@ -113,37 +258,120 @@ struct ExecuteTokens {
  tokens: proc_macro2::TokenStream,
 }
 
+#[derive(Debug)]
+struct SelectAsyncTokens {
+ tokens: proc_macro2::TokenStream,
+}
+
+#[derive(Debug)]
+struct ExecuteAsyncTokens {
+ tokens: proc_macro2::TokenStream,
+}
+
 #[derive(Debug)]
 struct QueryParams {
  params: Punctuated<Expr, Token![,]>,
+ /// `name = expr` parameters, for `:name` placeholders.
+ named: Vec<(Ident, Expr)>,
 }
 
 impl Parse for QueryParams {
  fn parse(input: ParseStream) -> syn::Result<Self> {
-  Ok(QueryParams {
-   params: if input.peek(Token![,]) {
-    input.parse::<Token![,]>().unwrap();
-    input.parse_terminated(Expr::parse)?
-   } else {
-    Punctuated::new()
-   },
-  })
+  let exprs = if input.peek(Token![,]) {
+   input.parse::<Token![,]>().unwrap();
+   input.parse_terminated(Expr::parse)?
+  } else {
+   Punctuated::<Expr, Token![,]>::new()
+  };
+
+  let mut params = Punctuated::new();
+  let mut named = Vec::new();
+
+  for expr in exprs {
+   match expr {
+    Expr::Assign(syn::ExprAssign { left, right, .. }) => match *left {
+     Expr::Path(syn::ExprPath { path, .. }) if path.get_ident().is_some() => {
+      named.push((path.get_ident().unwrap().clone(), *right))
+     }
+     left => return Err(syn::Error::new(left.span(), "Expected a :name placeholder's name")),
+    },
+    expr => params.push(expr),
+   }
+  }
+
+  Ok(QueryParams { params, named })
  }
 }
 
 #[derive(Clone, Debug)]
 struct ResultType {
  container: Option<Ident>,
- contents: Option<Ident>,
+ contents: Contents,
 }
 
-// impl Parse for ResultType {
-//  fn parse(input: ParseStream) -> syn::Result<Self> {
-//   let path = input.parse::<syn::Path>();
-//   eprintln!("{:#?}", path);
-//   Ok(ResultType {})
-//  }
-// }
+#[derive(Clone, Debug)]
+enum Contents {
+ /// A struct, e.g. `Person`, whose members are filled in by column name.
+ Struct(Ident),
+ /// One column, e.g. `String` or `Option<i64>`, or a tuple of them, e.g. `(String, i64)`.
+ Columns(Box<Type>),
+ /// `_`, or no result type: an anonymous struct, typed by `name_Type` column names.
+ Inferred,
+}
+
+impl Contents {
+ fn parse(ty: &Type) -> Contents {
+  match ty {
+   Type::Infer(_) => Contents::Inferred,
+   Type::Tuple(tuple) if !tuple.elems.is_empty() => Contents::Columns(Box::new(ty.clone())),
+   _ if builtin_sql_type(ty).is_some() || option_inner_type(ty).is_some() => {
+    Contents::Columns(Box::new(ty.clone()))
+   }
+   Type::Path(syn::TypePath { qself: None, path })
+    if path.segments.len() == 1 && path.segments[0].arguments.is_empty() =>
+   {
+    Contents::Struct(path.segments[0].ident.clone())
+   }
+   _ => abort!(ty, "Expected a struct, a column type such as String, or a tuple of column types"),
+  }
+ }
+
+ /// Table of a `derive(Turbosql)` result struct.
+ fn table_name(&self) -> Option<String> {
+  match self {
+   Contents::Struct(ident) => Some(ident.to_string().to_lowercase()),
+   _ => None,
+  }
+ }
+}
+
+impl ResultType {
+ /// Splits off a `Vec`, `Option`, `Iter` or `Page` container, if present.
+ fn parse(ty: &Type) -> ResultType {
+  let segment = match ty {
+   Type::Path(syn::TypePath { qself: None, path }) if path.segments.len() == 1 => &path.segments[0],
+   _ => return ResultType { container: None, contents: Contents::parse(ty) },
+  };
+
+  if !["Vec", "Option", "Iter", "Page"].contains(&segment.ident.to_string().as_str()) {
+   return ResultType { container: None, contents: Contents::parse(ty) };
+  }
+
+  match &segment.arguments {
+   syn::PathArguments::AngleBracketed(syn::AngleBracketedGenericArguments { args, .. })
+    if args.len() == 1 =>
+   {
+    match args.first().unwrap() {
+     syn::GenericArgument::Type(contents) => {
+      ResultType { container: Some(segment.ident.clone()), contents: Contents::parse(contents) }
+     }
+     arg => abort!(arg, "Expected a type"),
+    }
+   }
+   _ => abort!(segment, "Expected one type argument, e.g. {}<String>", segment.ident),
+  }
+ }
+}
 
 #[derive(Debug)]
 struct MembersAndCasters {
@@ -153,42 +381,27 @@ struct MembersAndCasters {
 }
 
 impl MembersAndCasters {
- fn create(members: Vec<(Ident, Ident, usize)>) -> MembersAndCasters {
+ fn create(members: Vec<(Ident, Ident, usize)>, json_columns: &[String]) -> MembersAndCasters {
   let struct_members: Vec<_> = members.iter().map(|(name, ty, _i)| quote!(#name: #ty)).collect();
-  let row_casters =
-   members.iter().map(|(name, _ty, i)| quote!(#name: row.get(#i)?)).collect::<Vec<_>>();
+  let row_casters = members
+   .iter()
+   .map(|(name, _ty, i)| {
+    let column = name.to_string();
+    match json_columns.contains(&column) {
+     true => quote!(#name: ::turbosql::__get_json(row, #i, #column)?),
+     false => quote!(#name: ::turbosql::__get(row, #i, #column)?),
+    }
+   })
+   .collect::<Vec<_>>();
 
   Self { members, struct_members, row_casters }
  }
 }
 
-fn extract_explicit_members(columns: &[String]) -> Option<MembersAndCasters> {
- // let members: Vec<_> = columns
- //  .iter()
- //  .enumerate()
- //  .filter_map(|(i, cap)| {
- //   let col_name = cap;
- //   let mut parts: Vec<_> = col_name.split('_').collect();
- //   if parts.len() < 2 {
- //    return None;
- //   }
- //   let ty = parts.pop()?;
- //   let name = parts.join("_");
- //   Some((format_ident!("{}", name), format_ident!("{}", ty), i))
- //  })
- //  .collect();
-
- println!("extractexplicitmembers: {:#?}", columns);
-
- // MembersAndCasters::create(members);
- // syn::parse_str::<Ident>
-
- None
-}
-
-fn extract_stmt_members(stmt: &Statement, span: &Span) -> MembersAndCasters {
- let members: Vec<_> = stmt
-  .column_names()
+/// Members of an anonymous result struct, from `name_Type` column names, e.g. `name_String`.
+fn extract_stmt_members(stmt_info: &StatementInfo, span: &Span) -> MembersAndCasters {
+ let members: Vec<_> = stmt_info
+  .column_names
   .iter()
   .enumerate()
   .map(|(i, col_name)| {
@@ -207,7 +420,7 @@ fn extract_stmt_members(stmt: &Statement, span: &Span) -> MembersAndCasters {
    let ty = parts.pop().unwrap();
 
    match ty {
-    "i64" | "String" => (),
+    "i8" | "u8" | "i16" | "u16" | "i32" | "u32" | "i64" | "f64" | "bool" | "String" => (),
     _ => abort!(span, "Invalid type annotation \"_{}\", try e.g. _String or _i64.", ty),
    }
 
@@ -217,11 +430,7 @@ fn extract_stmt_members(stmt: &Statement, span: &Span) -> MembersAndCasters {
   })
   .collect();
 
- // let struct_members: Vec<_> = members.iter().map(|(name, ty, _i)| quote!(#name: #ty)).collect();
- // let row_casters: Vec<_> =
- //  members.iter().map(|(name, _ty, i)| quote!(#name: row.get(#i).unwrap())).collect();
-
- MembersAndCasters::create(members)
+ MembersAndCasters::create(members, &[])
 }
 
 enum ParseStatementType {
@@ -234,10 +443,14 @@ use ParseStatementType::{Execute, Select};
 struct StatementInfo {
  parameter_count: usize,
  column_names: Vec<String>,
+ /// Declared types of the result columns; `None` for expressions.
+ column_decltypes: Vec<Option<String>>,
+ /// Affinity, e.g. `Text`, of the column each `?` is bound to, where that can be told.
+ parameter_affinities: Vec<Option<&'static str>>,
 }
 
 impl StatementInfo {
- fn membersandcasters(&self) -> syn::parse::Result<MembersAndCasters> {
+ fn membersandcasters(&self, json_columns: &[String]) -> syn::parse::Result<MembersAndCasters> {
   Ok(MembersAndCasters::create(
    self
     .column_names
@@ -245,6 +458,7 @@ impl StatementInfo {
     .enumerate()
     .map(|(i, col_name)| Ok((syn::parse_str::<Ident>(col_name)?, format_ident!("None"), i)))
     .collect::<syn::parse::Result<Vec<_>>>()?,
+   json_columns,
   ))
  }
 }
@@ -256,6 +470,14 @@ struct MigrationsToml {
 }
 
 fn migrations_to_tempdb(migrations: &[String]) -> Connection {
+ migrations_to_tempdb_with(migrations, |_| ())
+}
+
+/// `migrations_to_tempdb`, calling `after_each` with the temp db after each migration.
+fn migrations_to_tempdb_with(
+ migrations: &[String],
+ mut after_each: impl FnMut(&Connection),
+) -> Connection {
  let tempdb = rusqlite::Connection::open_in_memory().unwrap();
 
  tempdb
@@ -264,10 +486,13 @@ fn migrations_to_tempdb(migrations: &[String]) -> Connection {
   )
   .unwrap();
 
- migrations.iter().for_each(|m| match tempdb.execute(m, params![]) {
-  Ok(_) => (),
-  Err(rusqlite::Error::ExecuteReturnedResults) => (), // pragmas
-  Err(e) => abort_call_site!("Running migrations on temp db: {:?}", e),
+ migrations.iter().for_each(|m| {
+  match tempdb.execute(m, params![]) {
+   Ok(_) => (),
+   Err(rusqlite::Error::ExecuteReturnedResults) => (), // pragmas
+   Err(e) => abort_call_site!("Running migrations on temp db: {:?}", e),
+  }
+  after_each(&tempdb);
  });
 
  tempdb
@@ -276,8 +501,8 @@ fn migrations_to_tempdb(migrations: &[String]) -> Connection {
 fn migrations_to_schema(migrations: &[String]) -> Result<String, rusqlite::Error> {
  Ok(
   migrations_to_tempdb(migrations)
-   .prepare("SELECT sql FROM sqlite_master WHERE type='table' ORDER BY sql")?
-   .query_map(params![], |row| Ok(row.get(0)?))?
+   .prepare("SELECT sql FROM sqlite_master WHERE type IN ('table', 'index') AND sql IS NOT NULL ORDER BY sql")?
+   .query_map(params![], |row| row.get(0))?
    .collect::<Result<Vec<String>, _>>()?
    .join("\n"),
  )
@@ -317,25 +542,112 @@ fn validate_sql<S: AsRef<str>>(sql: S) -> Result<StatementInfo, rusqlite::Error>
  Ok(StatementInfo {
   parameter_count: stmt.parameter_count(),
   column_names: stmt.column_names().into_iter().map(str::to_string).collect(),
+  column_decltypes: stmt.columns().iter().map(|c| c.decl_type().map(str::to_string)).collect(),
+  parameter_affinities: param_affinities(&tempdb, sql.as_ref(), stmt.parameter_count()),
  })
 }
 
+/// Affinity of the column each `?` is bound to, from the SQL around it and the column's declared
+/// type; see `sql.rs`. `None` where that can't be told.
+fn param_affinities(
+ tempdb: &Connection,
+ sql: &str,
+ parameter_count: usize,
+) -> Vec<Option<&'static str>> {
+ let tokens = sql::tokenize(sql);
+
+ let hints = match sql::param_hints(&tokens) {
+  Some(hints) if hints.len() == parameter_count => hints,
+  _ => return vec![None; parameter_count],
+ };
+
+ let tables = sql::tables(&tokens);
+
+ hints
+  .into_iter()
+  .map(|hint| match hint? {
+   sql::ParamHint::Integer => Some("Integer"),
+   sql::ParamHint::Column(qualifier, column) => {
+    let mut affinities = tables
+     .iter()
+     .filter(|(table, alias)| match &qualifier {
+      Some(q) => {
+       q.eq_ignore_ascii_case(table) || matches!(alias, Some(a) if q.eq_ignore_ascii_case(a))
+      }
+      None => true,
+     })
+     .filter_map(|(table, _)| {
+      tempdb
+       .query_row(
+        "SELECT type FROM pragma_table_info(?) WHERE name = ? COLLATE NOCASE",
+        params![table, column],
+        |row| row.get::<_, String>(0),
+       )
+       .ok()
+     })
+     .map(|decltype| declared_affinity(&decltype))
+     .collect::<Vec<_>>();
+
+    affinities.sort_unstable();
+    affinities.dedup();
+
+    match affinities.as_slice() {
+     [affinity] => *affinity,
+     _ => None,
+    }
+   }
+  })
+  .collect()
+}
+
 fn validate_sql_or_abort<S: AsRef<str> + std::fmt::Debug>(sql: S) -> StatementInfo {
  validate_sql(sql.as_ref()).unwrap_or_else(|e| {
   abort_call_site!(r#"Error validating SQL statement: "{}". SQL: {:?}"#, e, sql)
  })
 }
 
+/// A leading `db,` before the result type or SQL: any expression other than a literal, that is
+/// followed by a comma. (A result type is never followed by a comma.)
+fn parse_database(input: ParseStream) -> syn::Result<Option<Expr>> {
+ let fork = input.fork();
+
+ match fork.parse::<Expr>() {
+  Ok(Expr::Lit(_)) | Ok(Expr::Binary(_)) | Err(_) => Ok(None),
+  Ok(_) if !fork.peek(Token![,]) => Ok(None),
+  Ok(database) => {
+   input.advance_to(&fork);
+   input.parse::<Token![,]>()?;
+   Ok(Some(database))
+  }
+ }
+}
+
 fn do_parse_tokens(
  input: ParseStream,
  statement_type: ParseStatementType,
+ asynchronous: bool,
 ) -> syn::Result<proc_macro2::TokenStream> {
  let span = input.span();
 
+ // Optional database to run on, e.g. select!(db, Vec<Bookmark>)
+
+ let database = parse_database(input)?;
+
  // Get result type and SQL
 
  let result_type = input.parse::<Type>().ok();
- let sql = input.parse::<LitStr>().ok().map(|s| s.value());
+ let sql_lit = input.parse::<LitStr>().ok();
+
+ // :name and #{expr} placeholders become ?, and are bound once the parameters are parsed
+
+ let (sql, placeholders) = match &sql_lit {
+  Some(lit) => {
+   let (sql, placeholders) =
+    sql::rewrite_placeholders(&lit.value()).unwrap_or_else(|e| abort!(lit, "{}", e));
+   (Some(sql), placeholders)
+  }
+  None => (None, Vec::new()),
+ };
 
  // Try validating SQL as-is
 
@@ -354,55 +666,21 @@ fn do_parse_tokens(
 
  eprintln!("{:?}, {:?}, {:?}", quote!(#result_type).to_string(), sql, stmt_info);
 
- // Extract container type (e.g. Vec, Option) if present
+ // Extract container type (e.g. Vec, Option) if present; no result type means an anonymous struct
 
  let result_type = match result_type {
-  Some(syn::Type::Path(syn::TypePath { path: syn::Path { segments, .. }, .. }))
-   if segments.len() == 1 =>
-  {
-   let segment = segments.first().unwrap();
-   Some(match segment.ident.to_string().as_str() {
-    "Vec" | "Option" => match &segment.arguments {
-     syn::PathArguments::AngleBracketed(syn::AngleBracketedGenericArguments { args, .. })
-      if args.len() == 1 =>
-     {
-      let arg = args.first().unwrap();
-      match arg {
-       syn::GenericArgument::Type(syn::Type::Path(syn::TypePath {
-        path: syn::Path { segments, .. },
-        ..
-       }))
-        if segments.len() == 1 =>
-       {
-        let contents_segment = segments.first().unwrap();
-        ResultType {
-         container: Some(segment.ident.clone()),
-         contents: Some(contents_segment.ident.clone()),
-        }
-       }
-       syn::GenericArgument::Type(syn::Type::Infer(_)) => {
-        ResultType { container: Some(segment.ident.clone()), contents: None }
-       }
-       _ => abort_call_site!("No segments found for container type {:#?}", arg),
-      }
-     }
-     _ => abort_call_site!("No arguments found for container type"),
-    },
-    _ => ResultType { container: None, contents: Some(segment.ident.clone()) },
-   })
-  }
-  Some(_) => abort_call_site!("Could not parse result_type"),
-  None => None,
+  Some(result_type) => ResultType::parse(&result_type),
+  None => ResultType { container: None, contents: Contents::Inferred },
  };
 
  eprintln!("{:?}, {:?}, {:?}", result_type, sql, stmt_info);
 
  // If it didn't still validate and we have a non-inferred result type, try adding SELECT ... FROM
 
- let (sql, stmt_info) = match (result_type.clone(), sql, stmt_info) {
+ let (sql, stmt_info) = match (&result_type.contents, sql, stmt_info) {
   //
   // Have result type and SQL did not validate, try generating SELECT ... FROM
-  (Some(ResultType { contents: Some(contents), .. }), sql, None) => {
+  (Contents::Struct(contents), sql, None) => {
    let result_type = contents.to_string();
    let table_name = result_type.to_lowercase();
    let tables = TABLES.lock().unwrap();
@@ -418,7 +696,8 @@ fn do_parse_tokens(
    let column_names_str =
     table.columns.iter().map(|c| c.name.as_str()).collect::<Vec<_>>().join(", ");
 
-   let sql = format!("SELECT {} FROM {} {}", column_names_str, table_name, sql.unwrap_or_default());
+   let from = from_sql(&table_name, table.soft_delete);
+   let sql = format!("SELECT {} FROM {} {}", column_names_str, from, sql.unwrap_or_default());
 
    (sql.clone(), validate_sql_or_abort(sql))
   }
@@ -426,6 +705,9 @@ fn do_parse_tokens(
   // Otherwise, everything is validated, just unwrap
   (_, Some(sql), Some(stmt_info)) => (sql, stmt_info),
 
+  // SQL that doesn't validate either way, for a result type with no table to fill it in from
+  (_, Some(sql), None) => (sql.clone(), validate_sql_or_abort(sql)),
+
   _ => abort_call_site!("no predicate and no result type found"),
  };
 
@@ -441,7 +723,23 @@ fn do_parse_tokens(
 
  // get query params and validate their count against what the statement is expecting
 
- let QueryParams { params } = input.parse()?;
+ let QueryParams { params, named } = input.parse()?;
+
+ // Page<T> takes a cursor and a page size after the statement's own parameters
+
+ let (params, page_args) = match &result_type.container {
+  Some(container) if container == "Page" => {
+   let mut params = params.into_iter().collect::<Vec<_>>();
+   match (params.pop(), params.pop()) {
+    (Some(size), Some(cursor)) => (params.into_iter().collect(), Some((cursor, size))),
+    _ => abort!(span, "Expected a cursor and a page size after the parameters, e.g. select!(Page<T> \"...\", cursor, 50)"),
+   }
+  }
+  _ => (params, None),
+ };
+
+ let params =
+  bind_placeholders(params, named, &placeholders, sql_lit.as_ref().map_or(span, |lit| lit.span()));
 
  if params.len() != stmt_info.parameter_count {
   abort!(
@@ -458,6 +756,41 @@ fn do_parse_tokens(
   return Err(input.error("Expected parameters"));
  }
 
+ // check each parameter's type against the column it's bound to, e.g. no i64 for `name = ?`
+
+ let param_checks = params
+  .iter()
+  .zip(&stmt_info.parameter_affinities)
+  .filter_map(|(param, affinity)| {
+   let affinity = format_ident!("{}", (*affinity)?, span = param.span());
+   Some(quote_spanned! {param.span()=>
+    ::turbosql::__check_param::<::turbosql::affinity::#affinity, _>(&(#param));
+   })
+  })
+  .collect::<Vec<_>>();
+
+ // derive(Turbosql) result types run on their own #[turbosql(database = "...")] by default
+
+ let database = match (database, result_type.contents.table_name()) {
+  (Some(database), _) => quote!((#database)),
+  (None, Some(table_name)) => {
+   database_tokens(TABLES.lock().unwrap().get(&table_name).and_then(|t| t.database.as_deref()))
+  }
+  (None, None) => database_tokens(None),
+ };
+
+ // _async variants move the database and owned copies of the parameters to a worker thread
+
+ let (database, database_expr) = match asynchronous {
+  true => (quote!(__turbosql_db), database),
+  false => (database.clone(), database),
+ };
+
+ let bound_params = match asynchronous {
+  true => quote!(&__turbosql_params),
+  false => quote!(::turbosql::params![#params]),
+ };
+
  // if we return no columns, this should be an execute
 
  if stmt_info.column_names.is_empty() {
@@ -465,178 +798,472 @@ fn do_parse_tokens(
    abort_call_site!("No rows returned from SQL, use execute! instead.");
   }
 
-  return Ok(quote! {
+  let tokens = quote! {
   {
-   (|| -> Result<_, _> {
-    let db = ::turbosql::__TURBOSQL_DB.lock().unwrap();
-    let mut stmt = db.prepare_cached(#sql)?;
-    stmt.execute(::turbosql::params![#params])
-   })()
+   #database.__with_db(|__turbosql_db| -> Result<_, _> {
+    let mut stmt = __turbosql_db.prepare_cached(#sql)?;
+    stmt.execute(#bound_params)
+   })
   }
-  });
+  };
+
+  return Ok(with_param_checks(
+   spawn_if_async(tokens, asynchronous, &database_expr, &params),
+   &param_checks,
+  ));
  }
 
  if !matches!(statement_type, Select) {
   abort_call_site!("Rows returned from SQL, use select! instead.");
  }
 
- // dispatch
-
- // let (struct_members, row_casters) = match (&result_type, &stmt_info, explicit_members) {
- //  (Some(_result_type), stmt_info, None) => {
- //   let members: Vec<_> = stmt_info
- //    .column_names
- //    .iter()
- //    .enumerate()
- //    .map(|(i, col_name)| (format_ident!("{}", col_name), format_ident!("None"), i))
- //    .collect();
-
- //   let m = MembersAndCasters::create(members);
-
- //   (m.struct_members, m.row_casters)
- //  }
-
- //  _ => abort!(span, "Expected explicitly typed return values or a return type."),
- // };
-
- // let struct_decl = None;
- // let (result_type, struct_decl) = match &result_type {
- //  Some(result_type) => (result_type, None),
- //  // Some(ResultType { contents, .. }) => (quote!(#contents), None),
- //  // Some(t) => (quote!(#t), None, Some(quote!(, ..Default::default()))),
- //  None => {
- //   let tsr = format_ident!("TurbosqlResult");
- //   (
- //    quote!(#tsr),
- //    Some(quote! {
- //     #[derive(Debug, Clone, ::turbosql::Serialize)]
- //     struct #tsr { #(#struct_members),* }
- //    }),
- //   )
- //  }
- // };
-
- let tokens = match result_type {
-  //
-  // Vec
-  Some(ResultType { container: Some(container), contents: Some(contents) })
-   if container == "Vec" =>
-  {
+ // #[turbosql(json)] members of derive(Turbosql) result types are decoded with serde_json
+
+ let json_columns = match result_type.contents.table_name() {
+  Some(table_name) => TABLES
+   .lock()
+   .unwrap()
+   .get(&table_name)
+   .map(|t| t.columns.iter().filter(|c| c.json).map(|c| c.name.clone()).collect())
+   .unwrap_or_default(),
+  None => Vec::new(),
+ };
+
+ // how to build one row, as a Result, checking column and tuple types against the statement
+
+ let (row_type, row, struct_decl) = match &result_type.contents {
+  Contents::Struct(contents) => {
    let m = stmt_info
-    .membersandcasters()
+    .membersandcasters(&json_columns)
     .unwrap_or_else(|_| abort_call_site!("stmt_info.membersandcasters failed"));
    let row_casters = m.row_casters;
 
+   (quote!(#contents), quote!(Ok(#contents { #(#row_casters),* })), None)
+  }
+
+  Contents::Columns(ty) => {
+   let ty: &Type = ty;
+   let types = match ty {
+    Type::Tuple(tuple) => tuple.elems.iter().collect(),
+    _ => vec![ty],
+   };
+
+   if types.len() != stmt_info.column_names.len() {
+    abort!(
+     ty,
+     "Expected {} column{} for {}, got {}: {:?}",
+     types.len(),
+     if types.len() == 1 { "" } else { "s" },
+     quote!(#ty).to_string(),
+     stmt_info.column_names.len(),
+     sql
+    );
+   }
+
+   for (i, ty) in types.iter().enumerate() {
+    check_column_type(ty, &stmt_info.column_names[i], stmt_info.column_decltypes[i].as_deref());
+   }
+
+   let row = match ty {
+    Type::Tuple(_) => {
+     let i = 0..types.len();
+     quote!(Ok((#(row.get(#i)?,)*)))
+    }
+    _ => quote!(row.get(0)),
+   };
+
+   (quote!(#ty), row, None)
+  }
+
+  Contents::Inferred => {
+   let m = extract_stmt_members(&stmt_info, &span);
+
+   for (_, ty, i) in &m.members {
+    let ty: Type = syn::parse_quote!(#ty);
+    check_column_type(&ty, &stmt_info.column_names[*i], stmt_info.column_decltypes[*i].as_deref());
+   }
+
+   let struct_members = m.struct_members;
+   let row_casters = m.row_casters;
+
+   (
+    quote!(TurbosqlResult),
+    quote!(Ok(TurbosqlResult { #(#row_casters),* })),
+    Some(quote! {
+     #[derive(Debug, Clone, PartialEq)]
+     struct TurbosqlResult { #(#struct_members),* }
+    }),
+   )
+  }
+ };
+
+ let owned_params = match asynchronous {
+  true => quote!(Ok(__turbosql_params)),
+  false => {
+   let params = params.iter();
+   quote!(vec![#(::turbosql::__to_value(&#params)),*].into_iter().collect())
+  }
+ };
+
+ let tokens = match result_type.container {
+  //
+  // Vec
+  Some(container) if container == "Vec" => {
    quote! {
     {
-     // #struct_decl
-     (|| -> Result<Vec<#contents>, ::turbosql::Error> {
-      let db = ::turbosql::__TURBOSQL_DB.lock().unwrap();
-      let mut stmt = db.prepare_cached(#sql)?;
-      let result = stmt.query_map(::turbosql::params![#params], |row| {
-       Ok(#contents {
-        #(#row_casters),*
-        // #default
-       })
-      })?.collect::<Vec<_>>();
-
-      let result = result.into_iter().flatten().collect::<Vec<_>>();
+     #struct_decl
+     #database.__with_reader(|__turbosql_db| -> Result<Vec<#row_type>, ::turbosql::Error> {
+      let mut stmt = __turbosql_db.prepare_cached(#sql)?;
+      let result = stmt.query_map(#bound_params, |row| -> Result<#row_type, _> {
+       #row
+      })?.collect::<Result<Vec<_>, _>>()?;
 
       Ok(result)
-     })()
+     })
     }
    }
   }
 
-  // Option
-  Some(ResultType { container: Some(container), contents: Some(contents) })
-   if container == "Option" =>
-  {
-   let m = stmt_info
-    .membersandcasters()
-    .unwrap_or_else(|_| abort_call_site!("stmt_info.membersandcasters failed"));
-   let row_casters = m.row_casters;
+  // Iter
+  Some(container) if container == "Iter" => {
+   quote! {
+    {
+     #struct_decl
+     #database.__iter(#sql, #owned_params, |row| -> Result<#row_type, _> {
+      #row
+     })
+    }
+   }
+  }
+
+  // Page
+  Some(container) if container == "Page" => {
+   let page_sql = page_sql(&sql, &stmt_info, span);
+
+   quote! {
+    {
+     #struct_decl
+     #database.__page(#page_sql, #owned_params, __turbosql_cursor.as_deref(), __turbosql_size, |row| -> Result<#row_type, _> {
+      #row
+     })
+    }
+   }
+  }
 
+  // Option
+  Some(container) if container == "Option" => {
    quote! {
     {
-     // #struct_decl
-     (|| -> Result<Option<#contents>, ::turbosql::Error> {
+     #struct_decl
+     #database.__with_reader(|__turbosql_db| -> Result<Option<#row_type>, ::turbosql::Error> {
       use ::turbosql::OptionalExtension;
 
-      let db = ::turbosql::__TURBOSQL_DB.lock().unwrap();
-      let mut stmt = db.prepare_cached(#sql)?;
-      let result = stmt.query_row(::turbosql::params![#params], |row| -> Result<#contents, _> {
-       Ok(#contents {
-        #(#row_casters),*
-        // #default
-       })
+      let mut stmt = __turbosql_db.prepare_cached(#sql)?;
+      let result = stmt.query_row(#bound_params, |row| -> Result<#row_type, _> {
+       #row
       }).optional()?;
 
       Ok(result)
-     })()
+     })
     }
    }
   }
 
-  // Primitive type
-  Some(ResultType { container: None, contents: Some(contents) })
-   if ["i64", "bool"].contains(&&contents.to_string().as_str()) =>
-  {
+  // One row
+  None => {
    quote! {
     {
-     (|| -> Result<#contents, ::turbosql::Error> {
-      let db = ::turbosql::__TURBOSQL_DB.lock().unwrap();
-      let mut stmt = db.prepare_cached(#sql)?;
-      let result = stmt.query_row(::turbosql::params![#params], |row| -> Result<#contents, _> {
-       Ok(row.get(0)?)
+     #struct_decl
+     #database.__with_reader(|__turbosql_db| -> Result<#row_type, ::turbosql::Error> {
+      let mut stmt = __turbosql_db.prepare_cached(#sql)?;
+      let result = stmt.query_row(#bound_params, |row| -> Result<#row_type, _> {
+       #row
       })?;
       Ok(result)
-     })()
+     })
     }
    }
   }
 
-  // Custom struct type
-  Some(ResultType { container: None, contents: Some(contents) }) => {
-   let m = stmt_info
-    .membersandcasters()
-    .unwrap_or_else(|_| abort_call_site!("stmt_info.membersandcasters failed"));
-   let row_casters = m.row_casters;
+  Some(container) => abort!(container, "Unknown container type"),
+ };
 
-   quote! {
-    {
-     (|| -> Result<#contents, ::turbosql::Error> {
-      let db = ::turbosql::__TURBOSQL_DB.lock().unwrap();
-      let mut stmt = db.prepare_cached(#sql)?;
-      let result = stmt.query_row(::turbosql::params![#params], |row| -> Result<#contents, _> {
-       Ok(#contents {
-        #(#row_casters),*
-        // #default
-       })
-      })?;
-      Ok(result)
-     })()
+ let tokens = spawn_if_async(tokens, asynchronous, &database_expr, &params);
+
+ // the cursor and page size are evaluated once, and owned, so select_async! can move them
+
+ let tokens = match page_args {
+  Some((cursor, size)) => quote! {
+   {
+    let __turbosql_cursor = ::turbosql::__page_cursor(#cursor);
+    let __turbosql_size: i64 = #size;
+    #tokens
+   }
+  },
+  None => tokens,
+ };
+
+ Ok(with_param_checks(tokens, &param_checks))
+}
+
+/// SQL for `select!(Page<T> ...)`: keyset pagination if the statement ends in `ORDER BY` result
+/// columns, otherwise `LIMIT`/`OFFSET`. Cursor values and the limit are bound after the
+/// statement's own parameters, with `?NNN` so that each can be used more than once.
+fn page_sql(sql: &str, stmt_info: &StatementInfo, span: Span) -> proc_macro2::TokenStream {
+ let order_by = sql::order_by(sql)
+  .unwrap_or_else(|_| abort!(span, "Page<T> adds its own LIMIT; remove the one in the SQL"));
+ let n = stmt_info.parameter_count;
+
+ let keys = order_by.as_ref().and_then(|o| o.columns.as_ref()).and_then(|columns| {
+  columns
+   .iter()
+   .map(|(column, descending)| {
+    let i = stmt_info.column_names.iter().position(|c| c.eq_ignore_ascii_case(column))?;
+    Some((i, *descending))
+   })
+   .collect::<Option<Vec<_>>>()
+ });
+
+ let (first, after, keys) = match (order_by, keys) {
+  (Some(order_by), Some(mut keys)) => {
+   let mut before = order_by.before.trim_end().to_string();
+   let mut column_names = stmt_info.column_names.clone();
+
+   // Rows with equal keys would be skipped at page boundaries, so rowid breaks ties. If it
+   // isn't a result column, it's added as a last one, which rows are decoded without. For
+   // SELECT DISTINCT, which rowid would undo, the other result columns break ties instead.
+
+   let distinct = matches!(
+    sql::tokenize(&before).as_slice(),
+    [sql::Token::Word(s), sql::Token::Word(d), ..]
+     if s.eq_ignore_ascii_case("SELECT") && d.eq_ignore_ascii_case("DISTINCT")
+   );
+
+   match column_names.iter().position(|c| c.eq_ignore_ascii_case("rowid")) {
+    Some(rowid) if keys.iter().any(|(i, _)| *i == rowid) => (),
+    Some(rowid) => keys.push((rowid, false)),
+    None if distinct => {
+     for i in 0..column_names.len() {
+      if !keys.iter().any(|(k, _)| *k == i) {
+       keys.push((i, false));
+      }
+     }
+    }
+    None => {
+     let from = sql::from_offset(&before).unwrap_or_else(|| {
+      abort!(span, "Page<T> needs a rowid to order rows with equal ORDER BY values")
+     });
+     before = format!(
+      "{}, rowid AS __turbosql_rowid {}",
+      before.chars().take(from).collect::<String>().trim_end(),
+      before.chars().skip(from).collect::<String>()
+     );
+     keys.push((column_names.len(), false));
+     column_names.push("__turbosql_rowid".to_string());
+
+     if let Err(e) = validate_sql(&before) {
+      abort!(
+       span,
+       "Page<T> needs a rowid to order rows with equal ORDER BY values: {}", e;
+       help = "select one as rowid, e.g. `b.rowid AS rowid`"
+      );
+     }
     }
    }
-  }
 
-  // Inferred
-  Some(ResultType { container: Some(container), contents: None }) => abort_call_site!("INFERRED"),
-  _ => abort_call_site!("unknown result_type"),
+   let column = |i: usize| format!("\"{}\"", column_names[i].replace('"', "\"\""));
+
+   let order = keys
+    .iter()
+    .map(|(i, descending)| format!("{} {}", column(*i), if *descending { "DESC" } else { "ASC" }))
+    .collect::<Vec<_>>()
+    .join(", ");
+
+   // (a < ?) OR (a IS ? AND b > ?) ... for ORDER BY a DESC, b ..., where NULLs sort first
+   // ascending and last descending, as in SQLite.
+
+   let after = (0..keys.len())
+    .map(|j| {
+     let mut terms =
+      (0..j).map(|k| format!("{} IS ?{}", column(keys[k].0), n + k + 1)).collect::<Vec<_>>();
+     let (c, p) = (column(keys[j].0), n + j + 1);
+     terms.push(match keys[j].1 {
+      true => format!("({c} < ?{p} OR (?{p} IS NOT NULL AND {c} IS NULL))", c = c, p = p),
+      false => format!("({c} > ?{p} OR (?{p} IS NULL AND {c} IS NOT NULL))", c = c, p = p),
+     });
+     format!("({})", terms.join(" AND "))
+    })
+    .collect::<Vec<_>>()
+    .join(" OR ");
+
+   let from = format!("SELECT * FROM ({})", before);
+
+   (
+    format!("{} ORDER BY {} LIMIT ?{}", from, order, n + 1),
+    format!("{} WHERE {} ORDER BY {} LIMIT ?{}", from, after, order, n + keys.len() + 1),
+    keys.iter().map(|(i, _)| *i).collect(),
+   )
+  }
+  _ => {
+   let sql = format!("{} LIMIT ?{} OFFSET ?{}", sql.trim_end(), n + 1, n + 2);
+   (sql.clone(), sql, Vec::new())
+  }
  };
 
- Ok(tokens)
+ validate_sql_or_abort(&first);
+ validate_sql_or_abort(&after);
+
+ quote!(&::turbosql::__PageSql { first: #first, after: #after, keys: &[#(#keys),*] })
+}
+
+/// Parameters in SQL order: positional parameters for `?`, `name = expr` parameters or else the
+/// local variable `name` for `:name`, and `expr` for `#{expr}`.
+fn bind_placeholders(
+ params: Punctuated<Expr, Token![,]>,
+ named: Vec<(Ident, Expr)>,
+ placeholders: &[sql::Placeholder],
+ sql_span: Span,
+) -> Punctuated<Expr, Token![,]> {
+ use sql::Placeholder::{Interpolated, Named, Other, Positional};
+
+ if !placeholders.iter().any(|p| matches!(p, Named(_) | Interpolated(_))) {
+  if let Some((name, _)) = named.first() {
+   abort!(name, "No :{} placeholder in the SQL", name);
+  }
+  return params;
+ }
+
+ if placeholders.contains(&Other) {
+  abort!(sql_span, "Can't mix :name or #{..} placeholders with ?NNN, @name or $name");
+ }
+
+ for (name, _) in &named {
+  if !placeholders.contains(&Named(name.to_string())) {
+   abort!(name, "No :{} placeholder in the SQL", name);
+  }
+ }
+
+ let positional_count = placeholders.iter().filter(|p| **p == Positional).count();
+
+ if params.len() != positional_count {
+  abort!(
+   sql_span,
+   "Expected {} positional parameter{} for ?, got {}",
+   positional_count,
+   if positional_count == 1 { "" } else { "s" },
+   params.len()
+  );
+ }
+
+ let mut params = params.into_iter();
+
+ placeholders
+  .iter()
+  .map(|placeholder| match placeholder {
+   Positional => params.next().unwrap(),
+   Named(name) => match named.iter().find(|(ident, _)| ident == name) {
+    Some((_, expr)) => expr.clone(),
+    None => {
+     let ident = Ident::new(name, sql_span);
+     syn::parse_quote!(#ident)
+    }
+   },
+   Interpolated(expr) => expr
+    .parse::<proc_macro2::TokenStream>()
+    .map_err(|e| e.to_string())
+    .and_then(|tokens| syn::parse2::<Expr>(respan(tokens, sql_span)).map_err(|e| e.to_string()))
+    .unwrap_or_else(|e| abort!(sql_span, "Invalid #{{..}} expression {:?}: {}", expr, e)),
+   Other => unreachable!(),
+  })
+  .collect()
+}
+
+/// Gives every token the span `span`, so that e.g. `#{expr}` resolves, and reports errors, at the
+/// SQL string it came from.
+fn respan(tokens: proc_macro2::TokenStream, span: Span) -> proc_macro2::TokenStream {
+ tokens
+  .into_iter()
+  .map(|mut token| {
+   if let proc_macro2::TokenTree::Group(group) = &token {
+    token = proc_macro2::Group::new(group.delimiter(), respan(group.stream(), span)).into();
+   }
+   token.set_span(span);
+   token
+  })
+  .collect()
+}
+
+/// Prepends parameter type checks, inside a closure that's never called, so that the parameter
+/// expressions are type checked but not evaluated twice.
+fn with_param_checks(
+ tokens: proc_macro2::TokenStream,
+ param_checks: &[proc_macro2::TokenStream],
+) -> proc_macro2::TokenStream {
+ if param_checks.is_empty() {
+  return tokens;
+ }
+
+ quote! {
+  {
+   #[allow(unused_parens)]
+   let _ = || {
+    #(#param_checks)*
+   };
+   #tokens
+  }
+ }
+}
+
+/// For select_async! and execute_async!, wraps the generated query in `::turbosql::spawn_blocking`.
+fn spawn_if_async(
+ tokens: proc_macro2::TokenStream,
+ asynchronous: bool,
+ database: &proc_macro2::TokenStream,
+ params: &Punctuated<Expr, Token![,]>,
+) -> proc_macro2::TokenStream {
+ if !asynchronous {
+  return tokens;
+ }
+
+ let params = params.iter();
+
+ quote! {
+  {
+   let __turbosql_db = #database.clone();
+   let __turbosql_params: Vec<::turbosql::Result<::turbosql::Value>> =
+    vec![#(::turbosql::__to_value(&#params)),*];
+
+   ::turbosql::spawn_blocking(move || {
+    match __turbosql_params.into_iter().collect::<::turbosql::Result<Vec<_>>>() {
+     Ok(__turbosql_params) => #tokens,
+     Err(e) => Err(e),
+    }
+   })
+  }
+ }
 }
 
 impl Parse for SelectTokens {
  fn parse(input: ParseStream) -> syn::Result<Self> {
-  Ok(SelectTokens { tokens: do_parse_tokens(input, Select)? })
+  Ok(SelectTokens { tokens: do_parse_tokens(input, Select, false)? })
  }
 }
 
 impl Parse for ExecuteTokens {
  fn parse(input: ParseStream) -> syn::Result<Self> {
-  Ok(ExecuteTokens { tokens: do_parse_tokens(input, Execute)? })
+  Ok(ExecuteTokens { tokens: do_parse_tokens(input, Execute, false)? })
+ }
+}
+
+impl Parse for SelectAsyncTokens {
+ fn parse(input: ParseStream) -> syn::Result<Self> {
+  Ok(SelectAsyncTokens { tokens: do_parse_tokens(input, Select, true)? })
+ }
+}
+
+impl Parse for ExecuteAsyncTokens {
+ fn parse(input: ParseStream) -> syn::Result<Self> {
+  Ok(ExecuteAsyncTokens { tokens: do_parse_tokens(input, Execute, true)? })
  }
 }
 
@@ -656,57 +1283,120 @@ pub fn select(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
  proc_macro::TokenStream::from(tokens)
 }
 
-/// Derive this on a `struct` to create a corresponding SQLite table and `insert`/`update`/`upsert` methods. (TODO: `Turbosql` trait?)
+/// Like [`execute!`], but runs on Turbosql's thread pool and returns a future.
+#[proc_macro]
+#[proc_macro_error]
+pub fn execute_async(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+ let ExecuteAsyncTokens { tokens } = parse_macro_input!(input);
+ proc_macro::TokenStream::from(tokens)
+}
+
+/// Like [`select!`], but runs on Turbosql's thread pool and returns a future.
+#[proc_macro]
+#[proc_macro_error]
+pub fn select_async(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+ let SelectAsyncTokens { tokens } = parse_macro_input!(input);
+ proc_macro::TokenStream::from(tokens)
+}
+
+/// Derive this on a `struct` to create a corresponding SQLite table and `insert`/`update`/`upsert`/`delete` methods. (TODO: `Turbosql` trait?)
+///
+/// Derive it on a fieldless `enum` to use it as a column type, stored as the variant name, or with `#[turbosql(repr = "int")]`, the discriminant.
 #[proc_macro_derive(Turbosql, attributes(turbosql))]
 #[proc_macro_error]
 pub fn turbosql_derive_macro(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
  // parse tokenstream and set up table struct
 
  let input = parse_macro_input!(input as DeriveInput);
+
+ if let Data::Enum(ref data) = input.data {
+  return proc_macro::TokenStream::from(enums::enum_derive(&input, data));
+ }
+
  let table_span = input.span();
  let table_ident = input.ident;
  let table_name = table_ident.to_string().to_lowercase();
 
  let ltn = LAST_TABLE_NAME.lock().unwrap().clone();
 
- let mut last_table_name_ref = LAST_TABLE_NAME.lock().unwrap();
- *last_table_name_ref = format!("{}, {}", ltn, table_name);
+ // not held for the rest of the derive, so that an abort doesn't poison it for later derives
+ *LAST_TABLE_NAME.lock().unwrap() = format!("{}, {}", ltn, table_name);
 
  let fields = match input.data {
   Data::Struct(ref data) => match data.fields {
    Fields::Named(ref fields) => fields,
    Fields::Unnamed(_) | Fields::Unit => unimplemented!(),
   },
-  Data::Enum(_) | Data::Union(_) => unimplemented!(),
+  Data::Enum(_) => unreachable!(),
+  Data::Union(_) => unimplemented!(),
  };
 
+ let columns = extract_columns(fields);
+ let TableAttrs { indexes, drop_columns, database, soft_delete } =
+  extract_table_attrs(&input.attrs, &columns);
+
  let table = Table {
   ident: table_ident,
+  vis: input.vis.clone(),
   span: table_span,
   name: table_name.clone(),
-  columns: extract_columns(fields),
+  columns,
+  indexes,
+  drop_columns,
+  database,
+  soft_delete,
  };
 
  let minitable = MiniTable {
-  name: table_name.clone(),
   columns: table
    .columns
    .iter()
    .map(|c| MiniColumn {
     name: c.name.clone(),
-    sql_type: c.sql_type,
-    rust_type: c.rust_type.clone(),
+    json: c.json,
+    unique: table.indexes.iter().any(|i| i.unique && i.columns == [c.name.as_str()]),
    })
    .collect(),
+  database: table.database.clone(),
+  soft_delete: table.soft_delete,
  };
 
  TABLES.lock().unwrap().insert(table_name, minitable);
 
+ check_references(&table);
+
  // create trait functions
 
  let fn_create = create::create(&table);
  let fn_insert = insert::insert(&table);
+ let fn_update = update::update(&table);
+ let fn_upsert = upsert::upsert(&table);
+ let fn_delete = delete::delete(&table);
  let fn_select = select::select(&table);
+ let fn_async = async_methods::async_methods(&table);
+ let fn_blob = blob::blob(&table);
+ let fn_query = query::query(&table);
+ let columns_struct = query::columns_struct(&table);
+ let impl_references = references::references(&table);
+
+ // make sure each member's type agrees with its column type
+
+ let column_checks = table.columns.iter().map(|c| {
+  let ty = &c.ty;
+  let affinity = &c.affinity;
+  let enum_check = c.enum_marker.as_ref().map(|marker| {
+   let inner_ty = option_inner_type(ty).unwrap_or(ty);
+   let marker = format_ident!("{}", marker);
+   quote_spanned!(c.span=> let _: () = <#inner_ty>::#marker;)
+  });
+  match c.json {
+   true => quote_spanned!(c.span=> ::turbosql::__check_json_column::<#ty>();),
+   false => quote_spanned! {c.span=>
+    ::turbosql::__check_column::<#ty, ::turbosql::affinity::#affinity>();
+    #enum_check
+   },
+  }
+ });
 
  // output tokenstream
 
@@ -714,8 +1404,23 @@ pub fn turbosql_derive_macro(input: proc_macro::TokenStream) -> proc_macro::Toke
   impl #table {
    #fn_create
    #fn_insert
+   #fn_update
+   #fn_upsert
+   #fn_delete
    #fn_select
+   #fn_async
+   #fn_blob
+   #fn_query
+
+   #[allow(dead_code)]
+   #[doc(hidden)]
+   fn __turbosql_check_columns() {
+    #(#column_checks)*
+   }
   }
+
+  #columns_struct
+  #impl_references
  })
 }
 
@@ -725,7 +1430,16 @@ fn extract_columns(fields: &FieldsNamed) -> Vec<Column> {
   .named
   .iter()
   .filter_map(|f| {
-   // Skip (skip) fields
+   // Skip (skip) fields, and look for an explicit sql_type or json
+
+   let mut explicit_sql_type = None;
+   let mut json = false;
+   let mut index = false;
+   let mut unique = false;
+   let mut references = None;
+   let mut on_delete = None;
+   let mut renamed_from = Vec::new();
+   let mut timestamp = None;
 
    for attr in &f.attrs {
     let meta = attr.parse_meta().unwrap();
@@ -739,6 +1453,34 @@ fn extract_columns(fields: &FieldsNamed) -> Vec<Column> {
           // require Option and manifest None values
           return None;
          }
+         Meta::Path(p) if p.is_ident("json") => json = true,
+         Meta::Path(p) if p.is_ident("index") => index = true,
+         Meta::Path(p) if p.is_ident("unique") => unique = true,
+         Meta::Path(p) if p.is_ident("created_at") || p.is_ident("updated_at") => {
+          if timestamp.is_some() {
+           abort!(p, "A member can't be both created_at and updated_at");
+          }
+          timestamp = Some(match p.is_ident("created_at") {
+           true => Timestamp::CreatedAt,
+           false => Timestamp::UpdatedAt,
+          });
+         }
+         Meta::NameValue(nv) if nv.path.is_ident("sql_type") => match &nv.lit {
+          syn::Lit::Str(lit) => explicit_sql_type = Some(lit.clone()),
+          lit => abort!(lit, "Expected a string, e.g. sql_type = \"INTEGER\""),
+         },
+         Meta::NameValue(nv) if nv.path.is_ident("references") => match &nv.lit {
+          syn::Lit::Str(lit) => references = Some(lit.clone()),
+          lit => abort!(lit, "Expected a string, e.g. references = \"Person\""),
+         },
+         Meta::NameValue(nv) if nv.path.is_ident("renamed_from") => match &nv.lit {
+          syn::Lit::Str(lit) => renamed_from.push(lit.value()),
+          lit => abort!(lit, "Expected a string, e.g. renamed_from = \"old_name\""),
+         },
+         Meta::NameValue(nv) if nv.path.is_ident("on_delete") => match &nv.lit {
+          syn::Lit::Str(lit) => on_delete = Some(lit.clone()),
+          lit => abort!(lit, "Expected a string, e.g. on_delete = \"cascade\""),
+         },
          _ => (),
         }
        }
@@ -752,43 +1494,119 @@ fn extract_columns(fields: &FieldsNamed) -> Vec<Column> {
    let name = ident.as_ref().unwrap().to_string();
 
    let ty = &f.ty;
-   let ty_str = quote!(#ty).to_string();
-
-   // TODO: have specific error messages or advice for other numeric types
-   // specifically, sqlite cannot represent u64 integers, would be coerced to float.
-   // https://sqlite.org/fileformat.html
-
-   let sql_type = match (name.as_str(), ty_str.as_str()) {
-    ("rowid", "Option < i64 >") => "INTEGER PRIMARY KEY",
-    // (_, "i64") => "INTEGER NOT NULL",
-    (_, "Option < i8 >") => "INTEGER",
-    (_, "Option < u8 >") => "INTEGER",
-    (_, "Option < i16 >") => "INTEGER",
-    (_, "Option < u16 >") => "INTEGER",
-    (_, "Option < i32 >") => "INTEGER",
-    (_, "Option < u32 >") => "INTEGER",
-    (_, "Option < i53 >") => "INTEGER",
-    (_, "Option < i64 >") => "INTEGER",
-    (_, "u64") => abort!(ty, SQLITE_64BIT_ERROR),
-    (_, "Option < u64 >") => abort!(ty, SQLITE_64BIT_ERROR),
-    // (_, "f64") => "REAL NOT NULL",
-    (_, "Option < f64 >") => "REAL",
-    // (_, "bool") => "BOOLEAN NOT NULL",
-    (_, "Option < bool >") => "BOOLEAN",
-    // (_, "String") => "TEXT NOT NULL",
-    (_, "Option < String >") => "TEXT",
-    // SELECT LENGTH(blob_column) ... will be null if blob is null
-    // (_, "Blob") => "BLOB NOT NULL",
-    (_, "Option < Blob >") => "BLOB",
-    _ => abort!(ty, "turbosql doesn't support rust type: {}", ty_str),
+
+   // Option<T> members are nullable columns; anything else is NOT NULL, with a
+   // default so that ALTER TABLE ADD COLUMN works on tables that already have rows.
+
+   let (nullable, inner_ty) = match option_inner_type(ty) {
+    Some(inner_ty) => (true, inner_ty),
+    None => (false, ty),
+   };
+
+   // The derive can't see trait impls, so the SQL type comes from the builtin types we know,
+   // or from #[turbosql(sql_type = "...")]. Either way, it's checked against
+   // TurbosqlType::Affinity at compile time.
+
+   let enum_values = match json {
+    true => None,
+    false => last_segment(inner_ty).and_then(|segment| {
+     ENUMS
+      .lock()
+      .unwrap()
+      .get(&segment.ident.to_string())
+      .map(|e| (e.sql_type.clone(), e.values.clone(), e.marker.clone()))
+    }),
+   };
+
+   let sql_type = match (&explicit_sql_type, builtin_sql_type(inner_ty)) {
+    (Some(lit), _) if json => abort!(lit, "json members are always stored as TEXT"),
+    _ if json => "TEXT".to_string(),
+    (Some(lit), _) => lit.value().to_uppercase(),
+    (None, Some(sql_type)) => sql_type.to_string(),
+    (None, None) if enum_values.is_some() => enum_values.as_ref().unwrap().0.clone(),
+    (None, None) => abort!(
+     ty,
+     "turbosql doesn't know the SQL type of rust type: {}",
+     quote!(#inner_ty).to_string();
+     help = "if it's a #[derive(Turbosql)] enum, declare it before this struct, since derives expand in order";
+     help = "otherwise, implement turbosql::TurbosqlType for it, and add #[turbosql(sql_type = \"INTEGER\")] (or REAL, TEXT, BLOB, BOOLEAN) to this member"
+    ),
+   };
+
+   // SELECT LENGTH(blob_column) ... will be null if blob is null
+   let (affinity, default) = match sql_type.as_str() {
+    "INTEGER" => ("Integer", "0"),
+    "REAL" => ("Real", "0.0"),
+    "TEXT" if json => ("Text", "'null'"),
+    "TEXT" => ("Text", "''"),
+    "BLOB" => ("Blob", "x''"),
+    "BOOLEAN" => ("Boolean", "FALSE"),
+    _ => abort!(
+     explicit_sql_type.as_ref().unwrap(),
+     "Unknown sql_type {:?}, expected one of INTEGER, REAL, TEXT, BLOB, BOOLEAN",
+     sql_type
+    ),
+   };
+
+   // enum members may only hold their variants, and default to the first one. Adding a variant
+   // changes the CHECK, so the table is rebuilt; see create::make_migrations.
+
+   let (default, check, enum_marker) = match enum_values {
+    Some((enum_sql_type, values, marker)) if enum_sql_type == sql_type => {
+     (values[0].clone(), format!(" CHECK ({} IN ({}))", name, values.join(", ")), Some(marker))
+    }
+    _ => (default.to_string(), String::new(), None),
+   };
+
+   // SQLite only allows ADD COLUMN ... REFERENCES if the column defaults to NULL
+
+   let references = references.map(|lit| {
+    if !nullable {
+     abort!(ty, "references members must be Option<_>, since SQLite requires them to default to NULL");
+    }
+    parse_reference(&lit, on_delete.as_ref())
+   });
+
+   if let (None, Some(lit)) = (&references, &on_delete) {
+    abort!(lit, "on_delete requires references");
+   }
+
+   if timestamp.is_some() && (json || !["i64", "f64"].contains(&quote!(#inner_ty).to_string().as_str())) {
+    abort!(ty, "created_at and updated_at members hold ms since the Unix epoch, so must be i64 or f64, or an Option of one");
+   }
+
+   let constraint = match &references {
+    Some(r) => format!(
+     "{} REFERENCES {}({}){}",
+     check,
+     r.table_name,
+     r.column,
+     r.on_delete.as_ref().map(|a| format!(" ON DELETE {}", a)).unwrap_or_default()
+    ),
+    None => check,
+   };
+
+   let sql_type = match (name.as_str(), nullable, quote!(#inner_ty).to_string().as_str()) {
+    ("rowid", true, "i64") => "INTEGER PRIMARY KEY".to_string(),
+    (_, true, _) => format!("{}{}", sql_type, constraint),
+    (_, false, _) => format!("{} NOT NULL DEFAULT {}{}", sql_type, default, constraint),
    };
 
    Some(Column {
     ident: ident.clone().unwrap(),
+    vis: f.vis.clone(),
     span: ty.span(),
-    rust_type: ty_str,
     name,
     sql_type,
+    ty: ty.clone(),
+    affinity: format_ident!("{}", affinity),
+    json,
+    enum_marker,
+    index,
+    unique,
+    references,
+    renamed_from,
+    timestamp,
    })
   })
   .collect::<Vec<_>>();
@@ -799,10 +1617,258 @@ fn extract_columns(fields: &FieldsNamed) -> Vec<Column> {
 
  if !matches!(
   columns.iter().find(|c| c.name == "rowid"),
-  Some(Column { sql_type: "INTEGER PRIMARY KEY", .. })
+  Some(Column { sql_type, .. }) if sql_type == "INTEGER PRIMARY KEY"
  ) {
   abort_call_site!("derive(Turbosql) structs must include a 'rowid: Option<i64>' field")
  };
 
  columns
 }
+
+/// SQLite only enforces a foreign key whose parent column is the rowid or has a unique index, so
+/// that each reference matches at most one row.
+fn check_references(table: &Table) {
+ for r in table.columns.iter().filter_map(|c| c.references.as_ref()) {
+  if r.column == "rowid" {
+   continue;
+  }
+
+  // abort without holding the lock, so that it isn't poisoned for later derives
+  let parent_unique = TABLES
+   .lock()
+   .unwrap()
+   .get(&r.table_name)
+   .map(|t| t.columns.iter().find(|c| r.column == c.name).map(|c| c.unique));
+
+  match parent_unique {
+   Some(Some(true)) => (),
+   Some(Some(false)) => abort!(
+    r.span,
+    "{}.{} isn't unique, so it can't be referenced",
+    r.table_ident,
+    r.column;
+    help = "reference {}'s rowid, or add #[turbosql(unique)] to {}", r.table_ident, r.column
+   ),
+   Some(None) => abort!(r.span, "{} has no member {}", r.table_ident, r.column),
+   None => abort!(
+    r.span,
+    "{} isn't known here, so {}.{} can't be checked for uniqueness",
+    r.table_ident,
+    r.table_ident,
+    r.column;
+    help = "declare {} before this struct, since derives expand in order", r.table_ident
+   ),
+  }
+ }
+}
+
+/// Parses `Struct` or `Struct.column` (default `rowid`), and an optional ON DELETE action.
+fn parse_reference(lit: &LitStr, on_delete: Option<&LitStr>) -> Reference {
+ let value = lit.value();
+ let mut parts = value.splitn(2, '.');
+
+ let table_ident = syn::parse_str::<Ident>(parts.next().unwrap())
+  .unwrap_or_else(|_| abort!(lit, "Expected a struct name, e.g. references = \"Person\""));
+ let column = syn::parse_str::<Ident>(parts.next().unwrap_or("rowid"))
+  .unwrap_or_else(|_| abort!(lit, "Expected a column name, e.g. references = \"Person.rowid\""));
+
+ let on_delete = on_delete.map(|lit| {
+  let action = lit.value().to_uppercase();
+  if !["CASCADE", "SET NULL", "SET DEFAULT", "RESTRICT", "NO ACTION"].contains(&action.as_str()) {
+   abort!(lit, "Expected one of cascade, set null, set default, restrict, no action");
+  }
+  action
+ });
+
+ Reference {
+  span: lit.span(),
+  table_name: table_ident.to_string().to_lowercase(),
+  table_ident: table_ident.clone(),
+  column: column.clone(),
+  on_delete,
+ }
+}
+
+/// Indexes from #[turbosql(index)] and #[turbosql(unique)] members, followed by struct-level
+/// #[turbosql(index(a, b))] and #[turbosql(unique(a, b))] composite indexes, columns
+/// retired with #[turbosql(drop_column = "name")], and #[turbosql(database = "name")].
+fn extract_table_attrs(attrs: &[syn::Attribute], columns: &[Column]) -> TableAttrs {
+ let mut drop_columns = Vec::new();
+ let mut database = None;
+ let mut soft_delete = false;
+
+ let mut indexes = columns
+  .iter()
+  .flat_map(|c| {
+   let index = Some(Index { columns: vec![c.name.clone()], unique: false }).filter(|_| c.index);
+   let unique = Some(Index { columns: vec![c.name.clone()], unique: true }).filter(|_| c.unique);
+   index.into_iter().chain(unique)
+  })
+  .collect::<Vec<_>>();
+
+ for attr in attrs {
+  match attr.parse_meta() {
+   Ok(Meta::List(list)) if list.path.is_ident("turbosql") => {
+    for value in list.nested.iter() {
+     match value {
+      NestedMeta::Meta(Meta::List(list))
+       if list.path.is_ident("index") || list.path.is_ident("unique") =>
+      {
+       let index_columns = list
+        .nested
+        .iter()
+        .map(|nested| match nested {
+         NestedMeta::Meta(Meta::Path(path)) => match path.get_ident() {
+          Some(ident) if columns.iter().any(|c| c.ident == *ident) => ident.to_string(),
+          _ => abort!(path, "No such column in this struct"),
+         },
+         _ => abort!(nested, "Expected column names, e.g. index(name, age)"),
+        })
+        .collect::<Vec<_>>();
+
+       if index_columns.is_empty() {
+        abort!(list, "Expected column names, e.g. index(name, age)");
+       }
+
+       indexes.push(Index { columns: index_columns, unique: list.path.is_ident("unique") });
+      }
+      NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("drop_column") => match &nv.lit {
+       syn::Lit::Str(lit) if columns.iter().any(|c| c.name == lit.value()) => {
+        abort!(lit, "Can't drop a column that is still a member of this struct")
+       }
+       syn::Lit::Str(lit) => drop_columns.push(lit.value()),
+       lit => abort!(lit, "Expected a string, e.g. drop_column = \"name\""),
+      },
+      NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("database") => match &nv.lit {
+       syn::Lit::Str(lit) if !lit.value().is_empty() => database = Some(lit.value()),
+       lit => abort!(lit, "Expected a database name, e.g. database = \"cache\""),
+      },
+      NestedMeta::Meta(Meta::Path(path)) if path.is_ident("soft_delete") => {
+       if let Some(c) = columns.iter().find(|c| c.name == "deleted_at") {
+        abort!(c.span, "soft_delete adds its own deleted_at column; please rename this member")
+       }
+       soft_delete = true;
+      }
+      _ => abort!(value, "Unknown turbosql attribute for structs"),
+     }
+    }
+   }
+   _ => (),
+  }
+ }
+
+ TableAttrs { indexes, drop_columns, database, soft_delete }
+}
+
+/// Aborts if a column declared e.g. `TEXT` is selected into e.g. an `i64`. Expressions have no
+/// declared type, and types Turbosql doesn't know have no SQL type, so those aren't checked.
+fn check_column_type(ty: &Type, column: &str, decltype: Option<&str>) {
+ let (sql_type, decltype) = match (builtin_sql_type(option_inner_type(ty).unwrap_or(ty)), decltype)
+ {
+  (Some(sql_type), Some(decltype)) => (sql_type, decltype),
+  _ => return,
+ };
+
+ let compatible = match (sql_type, column_affinity(decltype)) {
+  ("INTEGER", "INTEGER") | ("INTEGER", "NUMERIC") => true,
+  ("BOOLEAN", "INTEGER") | ("BOOLEAN", "NUMERIC") => true,
+  ("REAL", "REAL") | ("REAL", "INTEGER") | ("REAL", "NUMERIC") => true,
+  (sql_type, affinity) => sql_type == affinity,
+ };
+
+ if !compatible {
+  abort!(
+   ty,
+   "Column {:?} is declared {}, which doesn't fit {}",
+   column,
+   decltype,
+   quote!(#ty).to_string()
+  );
+ }
+}
+
+/// The `turbosql::affinity` type for a declared column type; `None` for typeless and `NUMERIC`
+/// columns, which take anything.
+fn declared_affinity(decltype: &str) -> Option<&'static str> {
+ match column_affinity(decltype) {
+  "INTEGER" => Some("Integer"),
+  "TEXT" => Some("Text"),
+  "REAL" => Some("Real"),
+  "BLOB" if !decltype.is_empty() => Some("Blob"),
+  "NUMERIC" if decltype.to_uppercase().contains("BOOL") => Some("Boolean"),
+  _ => None,
+ }
+}
+
+/// Column affinity of a declared type, per https://sqlite.org/datatype3.html#determination_of_column_affinity
+fn column_affinity(decltype: &str) -> &'static str {
+ let decltype = decltype.to_uppercase();
+
+ if decltype.contains("INT") {
+  "INTEGER"
+ } else if ["CHAR", "CLOB", "TEXT"].iter().any(|t| decltype.contains(t)) {
+  "TEXT"
+ } else if decltype.contains("BLOB") || decltype.is_empty() {
+  "BLOB"
+ } else if ["REAL", "FLOA", "DOUB"].iter().any(|t| decltype.contains(t)) {
+  "REAL"
+ } else {
+  "NUMERIC"
+ }
+}
+
+/// SQL type for the Rust types Turbosql knows without a `sql_type` attribute, by last path
+/// segment so that e.g. `std::string::String` works too.
+fn builtin_sql_type(ty: &Type) -> Option<&'static str> {
+ let segment = last_segment(ty)?;
+
+ let is_vec_u8 = || match &segment.arguments {
+  syn::PathArguments::AngleBracketed(syn::AngleBracketedGenericArguments { args, .. }) => {
+   matches!(args.first(), Some(syn::GenericArgument::Type(t)) if args.len() == 1 && quote!(#t).to_string() == "u8")
+  }
+  _ => false,
+ };
+
+ match segment.ident.to_string().as_str() {
+  "i8" | "u8" | "i16" | "u16" | "i32" | "u32" | "i64" | "isize" => Some("INTEGER"),
+  "u64" => abort!(ty, SQLITE_64BIT_ERROR),
+  "f64" => Some("REAL"),
+  "bool" => Some("BOOLEAN"),
+  "String" => Some("TEXT"),
+  "Blob" => Some("BLOB"),
+  "Vec" if is_vec_u8() => Some("BLOB"),
+  _ => None,
+ }
+}
+
+/// Last path segment of `ty`, e.g. `String` for `std::string::String`.
+fn last_segment(ty: &Type) -> Option<&syn::PathSegment> {
+ match ty {
+  Type::Path(syn::TypePath { qself: None, path }) => path.segments.last(),
+  _ => None,
+ }
+}
+
+/// If `ty` is `Option<T>`, returns `T`.
+fn option_inner_type(ty: &Type) -> Option<&Type> {
+ match ty {
+  Type::Path(syn::TypePath { qself: None, path }) => {
+   let segment = path.segments.last()?;
+   if segment.ident != "Option" {
+    return None;
+   }
+   match &segment.arguments {
+    syn::PathArguments::AngleBracketed(syn::AngleBracketedGenericArguments { args, .. })
+     if args.len() == 1 =>
+    {
+     match args.first()? {
+      syn::GenericArgument::Type(inner_ty) => Some(inner_ty),
+      _ => None,
+     }
+    }
+    _ => None,
+   }
+  }
+  _ => None,
+ }
+}
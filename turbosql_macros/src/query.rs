@@ -0,0 +1,81 @@
+use super::Table;
+use quote::{format_ident, quote, quote_spanned};
+
+/// `{Struct}Columns`, with a Column for each member, e.g. `Person::columns().name`. These live
+/// in their own struct, rather than as associated consts, so that members can share names with
+/// derived fns such as query().
+pub(super) fn columns_struct(table: &Table) -> proc_macro2::TokenStream {
+ let (ident, vis) = (&table.ident, &table.vis);
+ let columns_ident = format_ident!("{}Columns", ident);
+ let doc =
+  format!("The columns of [`{}`], for [`{}::query`]; see [`{}::columns`].", ident, ident, ident);
+
+ let fields = table.columns.iter().map(|c| {
+  let (ident, vis, ty) = (&c.ident, &c.vis, &c.ty);
+  let ty = match c.json {
+   true => quote!(::turbosql::Json<#ty>),
+   false => quote!(#ty),
+  };
+  quote_spanned!(c.span=> #vis #ident: ::turbosql::Column<#table, #ty>)
+ });
+
+ quote_spanned! { table.span =>
+  #[doc = #doc]
+  #[allow(dead_code)]
+  #[derive(Clone, Copy)]
+  #vis struct #columns_ident {
+   #(#fields),*
+  }
+ }
+}
+
+/// columns() and query()
+pub(super) fn query(table: &Table) -> proc_macro2::TokenStream {
+ let database = table.database();
+ let table_name = &table.name;
+ let column_names = table.columns.iter().map(|c| c.name.as_str()).collect::<Vec<_>>().join(", ");
+
+ let columns_ident = format_ident!("{}Columns", table.ident);
+ let columns = table.columns.iter().map(|c| {
+  let (ident, name) = (&c.ident, &c.name);
+  quote_spanned!(c.span=> #ident: ::turbosql::Column::__new(#name))
+ });
+
+ let row_casters = super::select::row_casters(table);
+ let from_row = quote! {
+  |row| {
+   #[allow(clippy::needless_update)]
+   Ok(#table { #(#row_casters),* , ..Default::default() })
+  }
+ };
+
+ let including_deleted = match table.soft_delete {
+  true => quote_spanned! { table.span =>
+   /// Like [`query`](Self::query), including deleted rows.
+   #[allow(dead_code)]
+   pub fn query_including_deleted() -> ::turbosql::Query<Self> {
+    ::turbosql::Query::__new(#database, #table_name, #column_names, #from_row)
+   }
+  },
+  false => quote!(),
+ };
+
+ let from = table.from();
+
+ quote_spanned! { table.span =>
+  /// This table's columns, for [`query`](Self::query), e.g. `Person::columns().name`.
+  #[allow(dead_code)]
+  pub fn columns() -> #columns_ident {
+   #columns_ident { #(#columns),* }
+  }
+
+  /// Starts a [`Query`](::turbosql::Query) on this table, to add filters, ordering and a limit to
+  /// at runtime.
+  #[allow(dead_code)]
+  pub fn query() -> ::turbosql::Query<Self> {
+   ::turbosql::Query::__new(#database, #from, #column_names, #from_row)
+  }
+
+  #including_deleted
+ }
+}
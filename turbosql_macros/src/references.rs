@@ -0,0 +1,59 @@
+use super::Table;
+use quote::{format_ident, quote_spanned};
+
+/// Accessors for #[turbosql(references = "...")] members: one on this struct returning the
+/// referenced row, and one on the referenced struct returning all rows that reference it.
+pub(super) fn references(table: &Table) -> proc_macro2::TokenStream {
+ let accessors = table.columns.iter().filter_map(|c| {
+  let r = c.references.as_ref()?;
+  let ident = &c.ident;
+  let parent = &r.table_ident;
+  let parent_column = &r.column;
+
+  // file_id -> file(), otherwise name it after the referenced table
+  let child_fn = match c.name.strip_suffix("_id") {
+   Some(stem) if !stem.is_empty() => format_ident!("{}", stem),
+   _ => format_ident!("{}", r.table_name),
+  };
+
+  // bookmarks(), or bookmarks_by_url() if Bookmark references the same struct more than once
+  let parent_fn = match table
+   .columns
+   .iter()
+   .filter(|c2| matches!(&c2.references, Some(r2) if r2.table_ident == r.table_ident))
+   .count()
+  {
+   1 => format_ident!("{}s", table.name),
+   _ => format_ident!("{}s_by_{}", table.name, c.name),
+  };
+
+  let child_where = format!("{} = ?", parent_column);
+  let parent_where = format!("{} = ?", c.name);
+
+  Some(quote_spanned! { c.span =>
+   impl #table {
+    /// Returns the row this one references, if any.
+    #[allow(dead_code)]
+    pub fn #child_fn(&self) -> ::turbosql::Result<Option<#parent>> {
+     use ::turbosql::OptionalExtension;
+     match &self.#ident {
+      Some(value) => #parent::select_one_where(#child_where, ::turbosql::params![value]).optional(),
+      None => Ok(None),
+     }
+    }
+   }
+
+   impl #parent {
+    /// Returns all rows that reference this one.
+    #[allow(dead_code)]
+    pub fn #parent_fn(&self) -> ::turbosql::Result<Vec<#table>> {
+     #table::select_where(#parent_where, ::turbosql::params![self.#parent_column])
+    }
+   }
+  })
+ });
+
+ quote_spanned! { table.span =>
+  #(#accessors)*
+ }
+}
@@ -3,102 +3,128 @@ use quote::quote;
 
 /// SELECT name1, name2... FROM tablename
 pub(super) fn select(table: &Table) -> proc_macro2::TokenStream {
- let sql = makesql_select(&table);
+ let sql = makesql_select(table);
 
  super::validate_sql_or_abort(&sql);
 
- let quotes = table
-  .columns
-  .iter()
-  .enumerate()
-  .map(|(i, c)| {
-   let ident = &c.ident;
-   quote!(#ident: row.get(#i).unwrap())
-  })
-  .collect::<Vec<_>>();
+ let database = table.database();
+
+ let quotes = row_casters(table);
 
  // The ..Default::default() is required to use structs with turbosql(skip) members
  // TODO: this requires #[derive(Turbosql)] structs to also impl/derive Default.
 
- quote! {
-  pub fn select_all() -> Vec<#table> {
-   let db = ::turbosql::__TURBOSQL_DB.lock().unwrap();
-   let mut stmt = db.prepare_cached(#sql).unwrap();
-
-   let iter = stmt.query_map(::turbosql::params![], |row| {
-    #[allow(clippy::needless_update)]
-    Ok(#table { #(#quotes),* , ..Default::default() })
-   }).unwrap();
-
-   let iter = iter.map(|row| row.unwrap());
+ let including_deleted = match table.soft_delete {
+  true => {
+   let sql = makesql_select_from(table, &table.name);
+   quote! {
+    /// Like [`select_all`](Self::select_all), including deleted rows.
+    #[allow(dead_code)]
+    pub fn select_all_including_deleted() -> Result<Vec<#table>, ::turbosql::Error> {
+     Self::select_sql(#sql, ::turbosql::params![])
+    }
+
+    /// Like [`select_where`](Self::select_where), including deleted rows.
+    #[allow(dead_code)]
+    pub fn select_where_including_deleted<P>(where_clause: &str, params: P) -> Result<Vec<#table>, ::turbosql::Error>
+    where
+    P: IntoIterator,
+    P::Item: ::turbosql::ToSql,
+    {
+     Self::select_sql(&format!("{} WHERE {}", #sql, where_clause), params)
+    }
+   }
+  }
+  false => quote!(),
+ };
 
-   iter.collect::<Vec<#table>>()
+ quote! {
+  /// All rows in the table.
+  #[allow(dead_code)]
+  pub fn select_all() -> Result<Vec<#table>, ::turbosql::Error> {
+   Self::select_sql(#sql, ::turbosql::params![])
   }
 
-  pub fn select_where<P>(where_clause: &str, params: P) -> Vec<#table>
+  /// Rows matching `where_clause`, e.g. `select_where("size > ?", params![1024])`.
+  #[allow(dead_code)]
+  pub fn select_where<P>(where_clause: &str, params: P) -> Result<Vec<#table>, ::turbosql::Error>
   where
   P: IntoIterator,
   P::Item: ::turbosql::ToSql,
   {
-   let sql = format!("{} WHERE {}", #sql, where_clause);
-
-   let db = ::turbosql::__TURBOSQL_DB.lock().unwrap();
-   let mut stmt = db.prepare_cached(&sql).unwrap();
-
-   let iter = stmt.query_map(params, |row| {
-    #[allow(clippy::needless_update)]
-    Ok(#table { #(#quotes),* , ..Default::default() })
-   }).unwrap();
-
-   let iter = iter.map(|row| row.unwrap());
-
-   iter.collect::<Vec<#table>>()
+   Self::select_sql(&format!("{} WHERE {}", #sql, where_clause), params)
   }
 
-  pub fn __select_sql<P>(sql: &str, params: P) -> Vec<#table>
+  /// Runs a full `SELECT` statement, whose columns must be this struct's members in order.
+  /// A value that can't be read into its member is an error naming its column.
+  #[allow(dead_code)]
+  pub fn select_sql<P>(sql: &str, params: P) -> Result<Vec<#table>, ::turbosql::Error>
   where
   P: IntoIterator,
   P::Item: ::turbosql::ToSql,
   {
-   let db = ::turbosql::__TURBOSQL_DB.lock().unwrap();
-   let mut stmt = db.prepare_cached(&sql).unwrap();
+   #database.__with_reader(|db| {
+    let mut stmt = db.prepare_cached(sql)?;
 
-   let iter = stmt.query_map(params, |row| {
-    #[allow(clippy::needless_update)]
-    Ok(#table { #(#quotes),* , ..Default::default() })
-   }).unwrap();
+    let iter = stmt.query_map(params, |row| {
+     #[allow(clippy::needless_update)]
+     Ok(#table { #(#quotes),* , ..Default::default() })
+    })?;
 
-   let iter = iter.map(|row| row.unwrap());
-
-   iter.collect::<Vec<#table>>()
+    iter.collect::<Result<Vec<#table>, _>>()
+   })
   }
 
+  /// The first row matching `where_clause`, or `Error::QueryReturnedNoRows`.
+  #[allow(dead_code)]
   pub fn select_one_where<P>(where_clause: &str, params: P) -> Result<#table, ::turbosql::Error>
   where
   P: IntoIterator,
   P::Item: ::turbosql::ToSql,
   {
    let sql = format!("{} WHERE {} LIMIT 1", #sql, where_clause);
-   // trace!("{}", sql);
 
-   let db = ::turbosql::__TURBOSQL_DB.lock().unwrap();
-   let mut stmt = db.prepare_cached(&sql).unwrap();
+   #database.__with_reader(|db| {
+    let mut stmt = db.prepare_cached(&sql)?;
 
-   stmt.query_row(params, |row| {
-    #[allow(clippy::needless_update)]
-    Ok(#table { #(#quotes),* , ..Default::default() })
+    stmt.query_row(params, |row| {
+     #[allow(clippy::needless_update)]
+     Ok(#table { #(#quotes),* , ..Default::default() })
+    })
    })
   }
 
+  #including_deleted
  }
 }
 
+/// member: value, reading each column of a row selected by makesql_select
+pub(super) fn row_casters(table: &Table) -> Vec<proc_macro2::TokenStream> {
+ table
+  .columns
+  .iter()
+  .enumerate()
+  .map(|(i, c)| {
+   let ident = &c.ident;
+   let name = &c.name;
+   match c.json {
+    true => quote!(#ident: ::turbosql::__get_json(row, #i, #name)?),
+    false => quote!(#ident: ::turbosql::__get(row, #i, #name)?),
+   }
+  })
+  .collect()
+}
+
 fn makesql_select(table: &Table) -> String {
+ makesql_select_from(table, &table.from())
+}
+
+fn makesql_select_from(table: &Table, from: &str) -> String {
  let mut sql = "SELECT ".to_string();
 
  sql += table.columns.iter().map(|c| c.name.as_str()).collect::<Vec<_>>().join(", ").as_str();
 
- sql += format!(" FROM {}", table.name).as_str();
+ sql += format!(" FROM {}", from).as_str();
 
  sql
 }
@@ -0,0 +1,640 @@
+//! A lexical scan of SQL statements: enough to tell which column each `?` is compared with or
+//! assigned to, without a full parser.
+
+#[derive(Clone, Debug, PartialEq)]
+pub(super) enum Token {
+ /// Identifier or keyword, unquoted.
+ Word(String),
+ /// `?` placeholder.
+ Param,
+ /// Any other placeholder, e.g. `?1` or `:name`.
+ OtherParam,
+ /// String, number or blob literal.
+ Literal,
+ Punct(String),
+}
+
+impl Token {
+ fn is_word(&self, word: &str) -> bool {
+  matches!(self, Token::Word(w) if w.eq_ignore_ascii_case(word))
+ }
+
+ fn is_punct(&self, punct: &str) -> bool {
+  matches!(self, Token::Punct(p) if p == punct)
+ }
+}
+
+pub(super) fn tokenize(sql: &str) -> Vec<Token> {
+ tokenize_with_offsets(sql).into_iter().map(|(token, _)| token).collect()
+}
+
+/// Tokens, each with the char offset in `sql` where it starts.
+fn tokenize_with_offsets(sql: &str) -> Vec<(Token, usize)> {
+ let chars: Vec<char> = sql.chars().collect();
+ let is_word_char = |c: char| c.is_alphanumeric() || c == '_';
+ let mut tokens = Vec::new();
+ let mut i = 0;
+
+ while i < chars.len() {
+  let c = chars[i];
+  let next = chars.get(i + 1).copied();
+  let offset = i;
+
+  match c {
+   c if c.is_whitespace() => i += 1,
+   '-' if next == Some('-') => {
+    while i < chars.len() && chars[i] != '\n' {
+     i += 1;
+    }
+   }
+   '/' if next == Some('*') => {
+    i += 2;
+    while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+     i += 1;
+    }
+    i += 2;
+   }
+   '\'' => {
+    i = skip_quoted(&chars, i, '\'');
+    tokens.push((Token::Literal, offset));
+   }
+   '"' | '`' | '[' => {
+    let start = i + 1;
+    i = skip_quoted(&chars, i, if c == '[' { ']' } else { c });
+    tokens.push((Token::Word(chars[start..(i - 1).max(start)].iter().collect()), offset));
+   }
+   '?' => {
+    i += 1;
+    match chars.get(i) {
+     Some(c) if c.is_ascii_digit() => {
+      while i < chars.len() && chars[i].is_ascii_digit() {
+       i += 1;
+      }
+      tokens.push((Token::OtherParam, offset));
+     }
+     _ => tokens.push((Token::Param, offset)),
+    }
+   }
+   ':' | '@' | '$' if matches!(next, Some(c) if is_word_char(c)) => {
+    i += 1;
+    while i < chars.len() && is_word_char(chars[i]) {
+     i += 1;
+    }
+    tokens.push((Token::OtherParam, offset));
+   }
+   'x' | 'X' if next == Some('\'') => {
+    i = skip_quoted(&chars, i + 1, '\'');
+    tokens.push((Token::Literal, offset));
+   }
+   c if is_word_char(c) => {
+    let start = i;
+    while i < chars.len() && is_word_char(chars[i]) {
+     i += 1;
+    }
+    let token = match c.is_ascii_digit() {
+     true => Token::Literal,
+     false => Token::Word(chars[start..i].iter().collect()),
+    };
+    tokens.push((token, offset));
+   }
+   _ => {
+    let two: String = chars[i..(i + 2).min(chars.len())].iter().collect();
+    let punct = match two.as_str() {
+     "<=" | ">=" | "<>" | "!=" | "==" | "||" | "<<" | ">>" => two,
+     _ => c.to_string(),
+    };
+    i += punct.len();
+    tokens.push((Token::Punct(punct), offset));
+   }
+  }
+ }
+
+ tokens
+}
+
+/// Index just past the quote that closes the one at `start`; doubled quotes are escapes.
+fn skip_quoted(chars: &[char], start: usize, close: char) -> usize {
+ let mut i = start + 1;
+ while i < chars.len() {
+  if chars[i] == close {
+   if close != ']' && chars.get(i + 1) == Some(&close) {
+    i += 2;
+    continue;
+   }
+   return i + 1;
+  }
+  i += 1;
+ }
+ chars.len()
+}
+
+/// What a `?` is bound to.
+#[derive(Clone, Debug, PartialEq)]
+pub(super) enum ParamHint {
+ /// Compared with or assigned to a column, e.g. `name = ?`, optionally qualified by a table
+ /// name or alias.
+ Column(Option<String>, String),
+ /// `LIMIT ?` or `OFFSET ?`.
+ Integer,
+}
+
+const COMPARISONS: &[&str] = &["=", "==", "!=", "<>", "<", "<=", ">", ">="];
+
+fn is_comparison(token: &Token) -> bool {
+ COMPARISONS.iter().any(|p| token.is_punct(p))
+  || ["LIKE", "GLOB", "IS"].iter().any(|w| token.is_word(w))
+}
+
+/// A column reference, e.g. `name` or `p.name`, ending at `tokens[end]`.
+fn column_ending_at(tokens: &[Token], end: Option<usize>) -> Option<ParamHint> {
+ let end = end?;
+ let name = match &tokens[end] {
+  Token::Word(name) => name.clone(),
+  _ => return None,
+ };
+
+ match end.checked_sub(2).map(|i| (&tokens[i], &tokens[i + 1])) {
+  Some((Token::Word(qualifier), dot)) if dot.is_punct(".") => {
+   Some(ParamHint::Column(Some(qualifier.clone()), name))
+  }
+  _ => Some(ParamHint::Column(None, name)),
+ }
+}
+
+/// A column reference, e.g. `name` or `p.name`, starting at `tokens[start]`.
+fn column_starting_at(tokens: &[Token], start: usize) -> Option<ParamHint> {
+ match tokens.get(start..start + 3) {
+  Some([Token::Word(qualifier), dot, Token::Word(name)]) if dot.is_punct(".") => {
+   Some(ParamHint::Column(Some(qualifier.clone()), name.clone()))
+  }
+  _ => match tokens.get(start) {
+   Some(Token::Word(name)) => Some(ParamHint::Column(None, name.clone())),
+   _ => None,
+  },
+ }
+}
+
+/// For each `?` in `tokens`, in order, what it's bound to, if that can be told from the tokens
+/// around it: `col = ?`, `? = col`, `col IN (?, ?)`, `col BETWEEN ? AND ?`, `LIMIT ?`,
+/// `OFFSET ?` and `INSERT INTO t (a, b) VALUES (?, ?)`. `None` if the statement has other kinds
+/// of placeholders, whose numbering this doesn't follow.
+pub(super) fn param_hints(tokens: &[Token]) -> Option<Vec<Option<ParamHint>>> {
+ if tokens.contains(&Token::OtherParam) {
+  return None;
+ }
+
+ let inserted = insert_columns(tokens);
+ let prev = |i: usize, n: usize| i.checked_sub(n).map(|i| &tokens[i]);
+
+ let hints = tokens
+  .iter()
+  .enumerate()
+  .filter(|(_, t)| **t == Token::Param)
+  .map(|(i, _)| {
+   if let Some(hint) = inserted.iter().find(|(j, _)| *j == i) {
+    return Some(hint.1.clone());
+   }
+
+   let before = prev(i, 1)?;
+
+   // LIMIT ?, LIMIT ?, ? and OFFSET ?
+
+   if before.is_word("LIMIT") || before.is_word("OFFSET") {
+    return Some(ParamHint::Integer);
+   }
+   if before.is_punct(",") && prev(i, 2) == Some(&Token::Param) && prev(i, 3)?.is_word("LIMIT") {
+    return Some(ParamHint::Integer);
+   }
+
+   // col = ?, col IS NOT ?, col NOT LIKE ?
+
+   if is_comparison(before) {
+    let end = match prev(i, 2) {
+     Some(t) if t.is_word("NOT") => i.checked_sub(3),
+     _ => i.checked_sub(2),
+    };
+    return column_ending_at(tokens, end);
+   }
+   if before.is_word("NOT") && matches!(prev(i, 2), Some(t) if t.is_word("IS")) {
+    return column_ending_at(tokens, i.checked_sub(3));
+   }
+
+   // col BETWEEN ? AND ?
+
+   let between = match before {
+    t if t.is_word("BETWEEN") => Some(i - 1),
+    t if t.is_word("AND") && matches!(prev(i, 3), Some(t) if t.is_word("BETWEEN")) => Some(i - 3),
+    _ => None,
+   };
+   if let Some(between) = between {
+    let end = match between.checked_sub(1).map(|j| &tokens[j]) {
+     Some(t) if t.is_word("NOT") => between.checked_sub(2),
+     _ => between.checked_sub(1),
+    };
+    return column_ending_at(tokens, end);
+   }
+
+   // col IN (?, ?)
+
+   let mut open = i - 1;
+   while tokens[open] == Token::Param || tokens[open].is_punct(",") {
+    open = open.checked_sub(1)?;
+   }
+   if tokens[open].is_punct("(") && matches!(prev(open, 1), Some(t) if t.is_word("IN")) {
+    let end = match prev(open, 2) {
+     Some(t) if t.is_word("NOT") => open.checked_sub(3),
+     _ => open.checked_sub(2),
+    };
+    return column_ending_at(tokens, end);
+   }
+
+   // ? = col
+
+   match tokens.get(i + 1) {
+    Some(t) if is_comparison(t) => column_starting_at(tokens, i + 2),
+    _ => None,
+   }
+  })
+  .collect();
+
+ Some(hints)
+}
+
+/// Placeholders in `INSERT INTO t (a, b) VALUES (?, ?), ...`, by token index, with their columns.
+fn insert_columns(tokens: &[Token]) -> Vec<(usize, ParamHint)> {
+ let mut result = Vec::new();
+
+ let into = match tokens.iter().position(|t| t.is_word("INTO")) {
+  Some(into) => into,
+  None => return result,
+ };
+
+ let table = match tokens.get(into + 1) {
+  Some(Token::Word(table)) => table.clone(),
+  _ => return result,
+ };
+
+ let mut i = into + 2;
+ let mut columns = Vec::new();
+
+ if !matches!(tokens.get(i), Some(t) if t.is_punct("(")) {
+  return result;
+ }
+ i += 1;
+ while let Some(Token::Word(column)) = tokens.get(i) {
+  columns.push(column.clone());
+  i += 1;
+  match tokens.get(i) {
+   Some(t) if t.is_punct(",") => i += 1,
+   Some(t) if t.is_punct(")") => break,
+   _ => return result,
+  }
+ }
+ i += 1;
+
+ if !matches!(tokens.get(i), Some(t) if t.is_word("VALUES")) {
+  return result;
+ }
+ i += 1;
+
+ // each parenthesized row of values; placeholders only count at the row's top level
+
+ while matches!(tokens.get(i), Some(t) if t.is_punct("(")) {
+  let (mut depth, mut column) = (0, 0);
+  i += 1;
+  while let Some(token) = tokens.get(i) {
+   match token {
+    t if t.is_punct("(") => depth += 1,
+    t if t.is_punct(")") && depth == 0 => break,
+    t if t.is_punct(")") => depth -= 1,
+    t if t.is_punct(",") && depth == 0 => column += 1,
+    Token::Param if depth == 0 => {
+     if let Some(name) = columns.get(column) {
+      result.push((i, ParamHint::Column(Some(table.clone()), name.clone())));
+     }
+    }
+    _ => (),
+   }
+   i += 1;
+  }
+  i += 1;
+  match tokens.get(i) {
+   Some(t) if t.is_punct(",") => i += 1,
+   _ => break,
+  }
+ }
+
+ result
+}
+
+/// Tables the statement reads or writes, with their aliases: `(table, alias)`.
+pub(super) fn tables(tokens: &[Token]) -> Vec<(String, Option<String>)> {
+ const NOT_ALIASES: &[&str] = &[
+  "WHERE",
+  "JOIN",
+  "INNER",
+  "LEFT",
+  "RIGHT",
+  "FULL",
+  "CROSS",
+  "OUTER",
+  "NATURAL",
+  "ON",
+  "USING",
+  "GROUP",
+  "ORDER",
+  "LIMIT",
+  "SET",
+  "VALUES",
+  "SELECT",
+  "UNION",
+  "EXCEPT",
+  "INTERSECT",
+  "HAVING",
+  "WINDOW",
+  "DEFAULT",
+  "RETURNING",
+  "INDEXED",
+  "NOT",
+ ];
+
+ let mut result = Vec::new();
+
+ for (i, token) in tokens.iter().enumerate() {
+  let starts_table = ["FROM", "JOIN", "INTO", "UPDATE"].iter().any(|w| token.is_word(w))
+   || (token.is_punct(",") && in_from_list(&tokens[..i]));
+
+  if !starts_table {
+   continue;
+  }
+
+  let table = match tokens.get(i + 1) {
+   Some(Token::Word(table)) => table.clone(),
+   _ => continue,
+  };
+
+  let alias = match (tokens.get(i + 2), tokens.get(i + 3)) {
+   (Some(as_), Some(Token::Word(alias))) if as_.is_word("AS") => Some(alias.clone()),
+   (Some(Token::Word(alias)), _) if !NOT_ALIASES.iter().any(|w| alias.eq_ignore_ascii_case(w)) => {
+    Some(alias.clone())
+   }
+   _ => None,
+  };
+
+  result.push((table, alias));
+ }
+
+ result
+}
+
+/// Whether the end of `tokens` is inside a `FROM` list, so that a comma there separates tables,
+/// as in `FROM a, b`.
+fn in_from_list(tokens: &[Token]) -> bool {
+ let mut depth = 0;
+ for token in tokens.iter().rev() {
+  match token {
+   t if t.is_punct(")") => depth += 1,
+   t if t.is_punct("(") && depth == 0 => return false,
+   t if t.is_punct("(") => depth -= 1,
+   t if depth == 0 && t.is_word("FROM") => return true,
+   t if depth == 0
+    && ["WHERE", "ON", "SELECT", "SET", "GROUP", "ORDER", "VALUES"]
+     .iter()
+     .any(|w| t.is_word(w)) =>
+   {
+    return false
+   }
+   _ => (),
+  }
+ }
+ false
+}
+
+/// A statement's top-level `ORDER BY`, e.g. `ORDER BY b.timestamp DESC, rowid`.
+#[derive(Debug)]
+pub(super) struct OrderBy {
+ /// The statement up to `ORDER BY`.
+ pub(super) before: String,
+ /// For each term, its column name and whether it's `DESC`, if every term is a plain column.
+ pub(super) columns: Option<Vec<(String, bool)>>,
+}
+
+/// The `ORDER BY` that ends the statement, if any; `Err` if the statement has its own `LIMIT`.
+pub(super) fn order_by(sql: &str) -> Result<Option<OrderBy>, String> {
+ let tokens = tokenize_with_offsets(sql);
+ let mut depth = 0;
+ let mut order_by = None;
+
+ for (i, (token, _)) in tokens.iter().enumerate() {
+  match token {
+   t if t.is_punct("(") => depth += 1,
+   t if t.is_punct(")") => depth -= 1,
+   t if depth == 0 && t.is_word("LIMIT") => return Err("LIMIT".to_string()),
+   t if depth == 0 && t.is_word("ORDER") => order_by = Some(i),
+   _ => (),
+  }
+ }
+
+ let i = match order_by {
+  Some(i) if matches!(tokens.get(i + 1), Some((t, _)) if t.is_word("BY")) => i,
+  _ => return Ok(None),
+ };
+
+ let before = sql.chars().take(tokens[i].1).collect::<String>();
+
+ let terms = tokens[i + 2..].split(|(t, _)| t.is_punct(","));
+ let columns = terms
+  .map(|term| {
+   let (descending, term) = match term.split_last() {
+    Some(((t, _), rest)) if t.is_word("DESC") => (true, rest),
+    Some(((t, _), rest)) if t.is_word("ASC") => (false, rest),
+    _ => (false, term),
+   };
+   match term {
+    [(Token::Word(column), _)]
+    | [(Token::Word(_), _), (Token::Punct(_), _), (Token::Word(column), _)]
+     if term.len() == 1 || term[1].0.is_punct(".") =>
+    {
+     Some((column.clone(), descending))
+    }
+    _ => None,
+   }
+  })
+  .collect();
+
+ Ok(Some(OrderBy { before, columns }))
+}
+
+/// Char offset of the statement's top-level `FROM`, if any.
+pub(super) fn from_offset(sql: &str) -> Option<usize> {
+ let mut depth = 0;
+
+ for (token, offset) in tokenize_with_offsets(sql) {
+  match token {
+   t if t.is_punct("(") => depth += 1,
+   t if t.is_punct(")") => depth -= 1,
+   t if depth == 0 && t.is_word("FROM") => return Some(offset),
+   _ => (),
+  }
+ }
+
+ None
+}
+
+/// The column definitions of a `CREATE TABLE` statement, as in `sqlite_master`: each column's
+/// name and the rest of its definition, e.g. `TEXT NOT NULL DEFAULT ''`. Table constraints are
+/// skipped.
+pub(super) fn column_defs(create_sql: &str) -> Vec<(String, String)> {
+ let chars: Vec<char> = create_sql.chars().collect();
+ let tokens = tokenize_with_offsets(create_sql);
+ let start = match tokens.iter().position(|(t, _)| t.is_punct("(")) {
+  Some(i) => i + 1,
+  None => return Vec::new(),
+ };
+
+ let mut defs = Vec::new();
+ let mut depth = 0;
+ let mut def_start = start;
+
+ for (i, (token, offset)) in tokens.iter().enumerate().skip(start) {
+  match token {
+   t if t.is_punct("(") => depth += 1,
+   t if t.is_punct(")") && depth > 0 => depth -= 1,
+   t if depth == 0 && (t.is_punct(",") || t.is_punct(")")) => {
+    match &tokens[def_start..i] {
+     [(Token::Word(name), _), rest @ ..]
+      if !["CONSTRAINT", "PRIMARY", "UNIQUE", "CHECK", "FOREIGN"]
+       .iter()
+       .any(|w| name.eq_ignore_ascii_case(w)) =>
+     {
+      let def = match rest.first() {
+       Some((_, def_offset)) => chars[*def_offset..*offset].iter().collect::<String>(),
+       None => String::new(),
+      };
+      defs.push((name.clone(), def.trim().to_string()));
+     }
+     _ => (),
+    }
+    if t.is_punct(")") {
+     break;
+    }
+    def_start = i + 1;
+   }
+   _ => (),
+  }
+ }
+
+ defs
+}
+
+/// `sql` with identifiers and keywords unquoted and lowercased, and tokens separated by single
+/// spaces, so that definitions that SQLite treats the same compare equal.
+pub(super) fn normalize(sql: &str) -> String {
+ let chars: Vec<char> = sql.chars().collect();
+ let tokens = tokenize_with_offsets(sql);
+
+ tokens
+  .iter()
+  .enumerate()
+  .map(|(i, (token, offset))| match token {
+   Token::Word(word) => word.to_lowercase(),
+   _ => {
+    let end = tokens.get(i + 1).map_or(chars.len(), |(_, next)| *next);
+    chars[*offset..end].iter().collect::<String>().trim().to_string()
+   }
+  })
+  .collect::<Vec<_>>()
+  .join(" ")
+}
+
+/// A placeholder in the SQL as written.
+#[derive(Clone, Debug, PartialEq)]
+pub(super) enum Placeholder {
+ /// `?`, bound from the next parameter after the SQL.
+ Positional,
+ /// `?NNN`, `@name` or `$name`, which Turbosql leaves alone.
+ Other,
+ /// `:name`, bound from a `name = expr` parameter, or else the local variable `name`.
+ Named(String),
+ /// `#{expr}`, bound from `expr`.
+ Interpolated(String),
+}
+
+/// Replaces `:name` and `#{expr}` placeholders with `?`, leaving strings, quoted identifiers and
+/// comments alone. Returns the rewritten SQL, and every placeholder in order.
+pub(super) fn rewrite_placeholders(sql: &str) -> Result<(String, Vec<Placeholder>), String> {
+ let chars: Vec<char> = sql.chars().collect();
+ let is_word_char = |c: char| c.is_alphanumeric() || c == '_';
+ let mut rewritten = String::with_capacity(sql.len());
+ let mut placeholders = Vec::new();
+ let mut i = 0;
+
+ while i < chars.len() {
+  let c = chars[i];
+  let next = chars.get(i + 1).copied();
+  let start = i;
+
+  match c {
+   '-' if next == Some('-') => {
+    while i < chars.len() && chars[i] != '\n' {
+     i += 1;
+    }
+   }
+   '/' if next == Some('*') => {
+    i += 2;
+    while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+     i += 1;
+    }
+    i = (i + 2).min(chars.len());
+   }
+   '\'' | '"' | '`' | '[' => i = skip_quoted(&chars, i, if c == '[' { ']' } else { c }),
+   '?' => {
+    i += 1;
+    match chars.get(i) {
+     Some(c) if c.is_ascii_digit() => placeholders.push(Placeholder::Other),
+     _ => placeholders.push(Placeholder::Positional),
+    }
+   }
+   '@' | '$' if matches!(next, Some(c) if is_word_char(c)) => {
+    i += 1;
+    placeholders.push(Placeholder::Other);
+   }
+   ':' if matches!(next, Some(c) if is_word_char(c) && !c.is_ascii_digit()) => {
+    i += 1;
+    while i < chars.len() && is_word_char(chars[i]) {
+     i += 1;
+    }
+    placeholders.push(Placeholder::Named(chars[start + 1..i].iter().collect()));
+    rewritten.push('?');
+    continue;
+   }
+   '#' if next == Some('{') => {
+    let mut depth = 0;
+    i += 1;
+    loop {
+     match chars.get(i) {
+      Some('{') => depth += 1,
+      Some('}') if depth == 1 => break,
+      Some('}') => depth -= 1,
+      Some(_) => (),
+      None => return Err(format!("Unclosed #{{ in SQL: {:?}", sql)),
+     }
+     i += 1;
+    }
+    let expr: String = chars[start + 2..i].iter().collect();
+    if expr.trim().is_empty() {
+     return Err(format!("Empty #{{}} in SQL: {:?}", sql));
+    }
+    placeholders.push(Placeholder::Interpolated(expr));
+    rewritten.push('?');
+    i += 1;
+    continue;
+   }
+   _ => i += 1,
+  }
+
+  rewritten.extend(&chars[start..i]);
+ }
+
+ Ok((rewritten, placeholders))
+}
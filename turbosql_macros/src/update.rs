@@ -0,0 +1,51 @@
+use super::{Table, Timestamp};
+use quote::{format_ident, quote_spanned};
+
+/// UPDATE tablename SET name1 = ?, name2 = ?... WHERE rowid = ?
+pub(super) fn update(table: &Table) -> proc_macro2::TokenStream {
+ let sql = makesql_update(table);
+
+ super::validate_sql_or_abort(&sql);
+
+ let database = table.database();
+
+ let columns = table
+  .columns
+  .iter()
+  .filter(|c| c.name != "rowid" && c.timestamp != Some(Timestamp::CreatedAt))
+  .chain(table.columns.iter().filter(|c| c.name == "rowid"))
+  .map(|c| c.bind(&format_ident!("self")))
+  .collect::<Vec<_>>();
+
+ // updated_at members are set to the time of the update; created_at members are left alone
+
+ let now = table.now();
+
+ quote_spanned! { table.span =>
+  /// Updates the row with this `rowid`. Returns 0 if there's no such row.
+  #[allow(dead_code)]
+  pub fn update(&self) -> ::turbosql::Result<usize> {
+   ::turbosql::__require_rowid(self.rowid, "update")?;
+   #now
+   #database.__with_db(|db| {
+    let mut stmt = db.prepare_cached(#sql)?;
+    stmt.execute(&[#(#columns),*] as &[&dyn ::turbosql::ToSql])
+   })
+  }
+ }
+}
+
+fn makesql_update(table: &Table) -> String {
+ let mut sql = format!("UPDATE {} SET ", table.name);
+ sql += table
+  .columns
+  .iter()
+  .filter(|c| c.name != "rowid" && c.timestamp != Some(Timestamp::CreatedAt))
+  .map(|c| format!("{} = ?", c.name))
+  .collect::<Vec<_>>()
+  .join(", ")
+  .as_str();
+ sql += " WHERE rowid = ?";
+
+ sql
+}
@@ -0,0 +1,54 @@
+use super::{Table, Timestamp};
+use quote::{format_ident, quote_spanned};
+
+/// INSERT INTO tablename (name1, name2...) VALUES (?1, ?2...) ON CONFLICT(rowid) DO UPDATE SET ...
+pub(super) fn upsert(table: &Table) -> proc_macro2::TokenStream {
+ let sql = makesql_upsert(table);
+
+ super::validate_sql_or_abort(&sql);
+
+ let database = table.database();
+
+ let columns = table.columns.iter().map(|c| c.bind(&format_ident!("self"))).collect::<Vec<_>>();
+
+ // like insert, then update: an existing row keeps its created_at
+
+ let now = table.now();
+
+ quote_spanned! { table.span =>
+  /// Inserts this row, or updates the row with this `rowid` if there is one, and returns its
+  /// `rowid`.
+  #[allow(dead_code)]
+  pub fn upsert(&self) -> ::turbosql::Result<i64> {
+   #now
+   #database.__with_db(|db| -> ::turbosql::Result<i64> {
+    let mut stmt = db.prepare_cached(#sql)?;
+    stmt.execute(&[#(#columns),*] as &[&dyn ::turbosql::ToSql])?;
+    Ok(self.rowid.unwrap_or_else(|| db.last_insert_rowid()))
+   })
+  }
+ }
+}
+
+fn makesql_upsert(table: &Table) -> String {
+ let mut sql = format!("INSERT INTO {} (", table.name);
+ sql += table.columns.iter().map(|c| c.name.as_str()).collect::<Vec<_>>().join(", ").as_str();
+ sql += ") VALUES (";
+ sql += table.columns.iter().map(|_| "?").collect::<Vec<_>>().join(", ").as_str();
+ sql += ") ON CONFLICT(rowid) DO ";
+
+ let updates = table
+  .columns
+  .iter()
+  .filter(|c| c.name != "rowid" && c.timestamp != Some(Timestamp::CreatedAt))
+  .map(|c| format!("{0} = excluded.{0}", c.name))
+  .collect::<Vec<_>>();
+
+ sql += match updates.is_empty() {
+  true => "NOTHING".to_string(),
+  false => format!("UPDATE SET {}", updates.join(", ")),
+ }
+ .as_str();
+
+ sql
+}